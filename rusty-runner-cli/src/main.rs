@@ -0,0 +1,267 @@
+//! A command line client for a [`rusty_runner_api`] server, usable as an ssh replacement in shell scripts.
+//!
+//! Prints the remote `stdout` and `stderr` to the local streams and exits with the remote exit code.
+//! If the command was terminated by a signal, exits with `128 + signal` like a shell.
+//! Errors of the client or the runner exit with 255, like ssh.
+
+use clap::{Parser, Subcommand, ValueHint};
+use rusty_runner_api::api::{
+    InfoResponse, OutputEncoding, RunRequest, RunResponse, RunScriptQuery, RunStatus,
+    ScriptInterpreter,
+};
+use rusty_runner_api::client::blocking::RunnerClient;
+use rusty_runner_api::client::ClientError;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// The exit code for errors of the client or the runner, analogous to ssh.
+const ERROR_EXIT_CODE: u8 = 255;
+
+/// Applies the [`RunOptions`] to a [`RunRequest`] or [`RunScriptQuery`], which share their builder methods.
+macro_rules! with_options {
+    ($request:expr, $options:expr) => {{
+        let options: RunOptions = $options;
+        let mut request = $request
+            .capture_stdout()
+            .capture_stderr()
+            .output_encoding(OutputEncoding::Base64);
+        if let Some(user) = options.user {
+            request = request.user(user);
+        }
+        if let Some(group) = options.group {
+            request = request.group(group);
+        }
+        if let Some(sandbox) = options.sandbox {
+            request = request.sandbox(sandbox);
+        }
+        if options.tty {
+            request = request.tty();
+        }
+        if options.kill_orphans {
+            request = request.kill_orphans();
+        }
+        if let Some(job_id) = options.job_id {
+            request = request.job_id(job_id);
+        }
+        request
+    }};
+}
+
+fn main() -> ExitCode {
+    let args = CliArgs::parse();
+    match execute(args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("rusty-runner: {e}");
+            ExitCode::from(ERROR_EXIT_CODE)
+        }
+    }
+}
+
+fn execute(args: CliArgs) -> Result<ExitCode, String> {
+    let client = RunnerClient::new(&args.url).map_err(|e| e.to_string())?;
+    match args.command {
+        Command::Info => {
+            let info = client.info().map_err(|e| e.to_string())?;
+            print_info(&info);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Run {
+            options,
+            command,
+            arguments,
+        } => {
+            let request = with_options!(RunRequest::new(command).args(arguments), options);
+            let response = client.run(&request, None).map_err(|e| e.to_string())?;
+            print_response(response)
+        }
+        Command::Script {
+            options,
+            interpreter,
+            strict,
+            file,
+            arguments,
+        } => {
+            let script = if file.as_os_str() == "-" {
+                std::io::read_to_string(std::io::stdin())
+            } else {
+                std::fs::read_to_string(&file)
+            }
+            .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+            let mut query = RunScriptQuery::new(interpreter).args(arguments);
+            if strict {
+                query = query.strict();
+            }
+            let query = with_options!(query, options);
+            let response = client
+                .run_script(&query, script, None)
+                .map_err(|e| e.to_string())?;
+            print_response(response)
+        }
+        Command::Get { remote, local } => {
+            let content = client.fetch_file(&remote).map_err(|e| match e {
+                ClientError::Status { status: 404, .. } => format!("{remote}: no such remote file"),
+                e => e.to_string(),
+            })?;
+            if local.as_os_str() == "-" {
+                std::io::stdout().write_all(&content)
+            } else {
+                std::fs::write(&local, content)
+            }
+            .map_err(|e| format!("failed to write {}: {e}", local.display()))?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Prints the info of the server, one `key: value` per line, indenting the entries of lists.
+fn print_info(info: &InfoResponse) {
+    let limit =
+        |limit: Option<u64>| limit.map_or(String::from("unlimited"), |limit| limit.to_string());
+    println!("computer_name: {}", info.computer_name);
+    println!("os_type: {:?}", info.os_type);
+    println!("api_version: {}", info.api_version);
+    if let Some(server_version) = &info.server_version {
+        println!("server_version: {server_version}");
+    }
+    println!("interpreters:");
+    for interpreter in &info.interpreters {
+        match &interpreter.version {
+            Some(version) => println!("  {}: {version}", interpreter.interpreter),
+            None => println!("  {}", interpreter.interpreter),
+        }
+    }
+    println!("features: {}", info.features.join(", "));
+    println!("limits:");
+    println!(
+        "  max_upload_bytes: {}",
+        limit(info.limits.max_upload_bytes)
+    );
+    println!(
+        "  max_output_bytes: {}",
+        limit(info.limits.max_output_bytes)
+    );
+}
+
+/// Prints the remote output to the local streams and returns the remote exit code.
+fn print_response(response: RunResponse) -> Result<ExitCode, String> {
+    match response.status {
+        RunStatus::Completed {
+            exit_code,
+            signal,
+            stdout,
+            stderr,
+            ..
+        } => {
+            write_output(&mut std::io::stdout(), stdout.as_deref())
+                .and_then(|()| write_output(&mut std::io::stderr(), stderr.as_deref()))
+                .map_err(|e| format!("failed to write output: {e}"))?;
+            let code = match signal {
+                Some(signal) => signal.number.saturating_add(128),
+                None => exit_code,
+            };
+            Ok(ExitCode::from(
+                u8::try_from(code).unwrap_or(ERROR_EXIT_CODE),
+            ))
+        }
+        RunStatus::Failure { reason } => Err(format!("job {} failed: {reason}", response.id)),
+    }
+}
+
+fn write_output(stream: &mut impl Write, output: Option<&[u8]>) -> std::io::Result<()> {
+    stream.write_all(output.unwrap_or_default())?;
+    stream.flush()
+}
+
+/// A command line client for a rusty-runner server.
+///
+/// Prints the remote `stdout` and `stderr` to the local streams and exits with the remote exit code.
+/// Errors of the client or the runner exit with 255.
+#[derive(Parser)]
+#[command(name = "rusty-runner", version)]
+struct CliArgs {
+    /// The url of the rusty-runner server.
+    #[arg(
+        long,
+        value_name = "URL",
+        value_hint = ValueHint::Url,
+        default_value = "http://localhost:8000",
+        env = "RUSTY_RUNNER_URL",
+        global = true,
+    )]
+    url: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the information of the server.
+    Info,
+    /// Runs a command, e.g. `rusty-runner run -- ls -la`.
+    Run {
+        #[command(flatten)]
+        options: RunOptions,
+        /// The command as available on the path of the server or a path to an executable.
+        #[arg(value_hint = ValueHint::CommandName)]
+        command: String,
+        /// The arguments of the command.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        arguments: Vec<String>,
+    },
+    /// Runs a local script file with an interpreter, e.g. `rusty-runner script --interpreter bash deploy.sh -- prod`.
+    Script {
+        #[command(flatten)]
+        options: RunOptions,
+        /// The interpreter that the script is run with, a builtin or a name configured on the server.
+        #[arg(short, long)]
+        interpreter: ScriptInterpreter,
+        /// Stop the script at the first failing command, e.g. `-euo pipefail` in bash.
+        #[arg(long)]
+        strict: bool,
+        /// The local script file, or `-` to read it from stdin.
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// The arguments of the script.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        arguments: Vec<String>,
+    },
+    /// Fetches a file from the working directory of the server, e.g. `rusty-runner get task/out.log out.log`.
+    Get {
+        /// The path relative to the working directory of the server.
+        remote: String,
+        /// The local file to write, or `-` to print it to stdout.
+        #[arg(value_hint = ValueHint::FilePath)]
+        local: PathBuf,
+    },
+}
+
+/// The options shared by `run` and `script`.
+#[derive(clap::Args)]
+struct RunOptions {
+    /// The unix user to run the command as, which must be allowed by the server.
+    #[arg(long)]
+    user: Option<String>,
+    /// The unix group to run the command as, which must be allowed by the server.
+    #[arg(long)]
+    group: Option<String>,
+    /// Run the command in a sandbox, or opt out by `--sandbox=false`. Defaults to the configuration of the server.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    sandbox: Option<bool>,
+    /// Attach a pseudo terminal. The terminal output is printed to stdout.
+    #[arg(long)]
+    tty: bool,
+    /// Kill the processes remaining in the process group once the command exits.
+    #[arg(long)]
+    kill_orphans: bool,
+    /// A unique id of the job, which is logged by the server.
+    #[arg(long)]
+    job_id: Option<String>,
+}
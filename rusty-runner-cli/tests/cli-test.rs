@@ -0,0 +1,258 @@
+//! Tests which call the cli binary, without a server or against a fake server answering with canned responses.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+fn rusty_runner() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rusty-runner"));
+    // Nothing listens on port 1.
+    command.args(["--url", "http://127.0.0.1:1"]);
+    command
+}
+
+#[test]
+fn unreachable_server() {
+    let output = rusty_runner()
+        .args(["run", "--", "echo", "Hello"])
+        .output()
+        .expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(255));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("rusty-runner: "));
+}
+
+#[test]
+fn missing_script() {
+    let output = rusty_runner()
+        .args(["script", "--interpreter", "bash", "does-not-exist.sh"])
+        .output()
+        .expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(255));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does-not-exist.sh"));
+}
+
+#[test]
+fn unknown_interpreter() {
+    let output = rusty_runner()
+        .args(["script", "--interpreter", "no such interpreter", "-"])
+        .output()
+        .expect("Couldn't run cli");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid interpreter name"));
+}
+
+/// A request received by the [`FakeServer`].
+#[derive(Debug, Clone)]
+struct Request {
+    /// The request line, e.g. `POST /api/run HTTP/1.1`.
+    line: String,
+    body: Vec<u8>,
+}
+
+/// A server answering every request with the same json response, recording the requests.
+struct FakeServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl FakeServer {
+    /// Starts the server on a free port, serving until the test exits.
+    fn spawn(response: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind a free port");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("listener has an address")
+        );
+        let requests = Arc::default();
+        let recorded = Arc::clone(&requests);
+        let response = response.to_owned();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // Broken connections fail the cli, which is checked by the tests.
+                let _ = respond(&stream, &response, &recorded);
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn cli(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rusty-runner"));
+        command.args(["--url", &self.url]);
+        command
+    }
+
+    /// The single request received so far.
+    fn request(&self) -> Request {
+        let requests = self.requests.lock().expect("requests lock poisoned");
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        requests[0].clone()
+    }
+}
+
+/// Reads a request, records it and then writes the `response`, closing the connection.
+fn respond(
+    stream: &TcpStream,
+    response: &str,
+    requests: &Mutex<Vec<Request>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or_default();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    requests
+        .lock()
+        .expect("requests lock poisoned")
+        .push(Request {
+            line: String::from(line.trim_end()),
+            body,
+        });
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )?;
+    stream.flush()
+}
+
+#[test]
+fn info() {
+    let server = FakeServer::spawn(
+        r#"{
+            "os_type": "Unix",
+            "computer_name": "runner",
+            "api_version": "3.0.0",
+            "interpreters": [{ "interpreter": "bash", "version": "GNU bash 5.2" }, { "interpreter": "sh" }],
+            "features": ["tty", "sandbox"],
+            "limits": { "max_upload_bytes": 1024 }
+        }"#,
+    );
+
+    let output = server.cli().arg("info").output().expect("Couldn't run cli");
+    assert!(output.status.success());
+    assert_eq!(server.request().line, "GET /api/info HTTP/1.1");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "computer_name: runner\n\
+         os_type: Unix\n\
+         api_version: 3.0.0\n\
+         interpreters:\n  bash: GNU bash 5.2\n  sh\n\
+         features: tty, sandbox\n\
+         limits:\n  max_upload_bytes: 1024\n  max_output_bytes: unlimited\n"
+    );
+}
+
+#[test]
+fn remote_output_and_exit_code() {
+    let server = FakeServer::spawn(
+        r#"{"id": 1, "status": "Completed", "exit_code": 3, "time_taken": {"secs": 0, "nanos": 0},
+            "stdout": "hello world\n", "stderr": "oops\n"}"#,
+    );
+    let script = b"echo \"hello $1\"\necho 'oops' >&2\nexit 3\n";
+    let mut child = server
+        .cli()
+        .args([
+            "script",
+            "--interpreter",
+            "bash",
+            "--sandbox=false",
+            "-",
+            "world",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Couldn't run cli");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(script)
+        .expect("Couldn't write script");
+    let output = child.wait_with_output().expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "oops\n");
+
+    let request = server.request();
+    assert!(
+        request.line.starts_with("POST /api/runscript?"),
+        "{request:?}"
+    );
+    for parameter in [
+        "interpreter=bash",
+        "sandbox=false",
+        "arguments=%5B%22world%22%5D",
+    ] {
+        assert!(request.line.contains(parameter), "{request:?}");
+    }
+    assert_eq!(request.body, script);
+}
+
+#[test]
+fn remote_run() {
+    let server = FakeServer::spawn(
+        r#"{"id": 1, "status": "Completed", "exit_code": 7, "time_taken": {"secs": 0, "nanos": 0},
+            "stdout": "out", "stderr": ""}"#,
+    );
+    let output = server
+        .cli()
+        .args(["run", "--", "sh", "-c", "printf out; exit 7"])
+        .output()
+        .expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(output.stdout, b"out");
+    assert!(output.stderr.is_empty());
+    let request = server.request();
+    assert_eq!(request.line, "POST /api/run HTTP/1.1");
+    let body = String::from_utf8_lossy(&request.body);
+    assert!(
+        body.contains(r#""arguments":["-c","printf out; exit 7"]"#),
+        "{body}"
+    );
+}
+
+#[test]
+fn remote_signal_and_failure() {
+    // Commands terminated by a signal exit like in a shell.
+    let server = FakeServer::spawn(
+        r#"{"id": 1, "status": "Completed", "exit_code": -1001, "time_taken": {"secs": 0, "nanos": 0},
+            "signal": {"number": 15, "name": "SIGTERM"}}"#,
+    );
+    let output = server
+        .cli()
+        .args(["run", "--", "sh", "-c", "kill -TERM $$"])
+        .output()
+        .expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(128 + 15));
+
+    let server = FakeServer::spawn(r#"{"id": 42, "status": "Failure", "reason": "Not supported"}"#);
+    let output = server
+        .cli()
+        .args(["run", "--", "true"])
+        .output()
+        .expect("Couldn't run cli");
+    assert_eq!(output.status.code(), Some(255));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rusty-runner: job 42 failed: Not supported\n"
+    );
+}
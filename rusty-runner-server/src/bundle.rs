@@ -0,0 +1,121 @@
+//! Multi-file script bundles for `POST /api/runscript/bundle`.
+//!
+//! The files are written to a fresh directory `bundle_{id}` in the working directory,
+//! which is also the working directory of the entrypoint, such that it can `source` its helpers.
+
+use crate::job::Job;
+use crate::process::{contained_path, working_directory};
+use axum::body::Bytes;
+use axum::extract::Multipart;
+use rusty_runner_api::api::BundleQuery;
+use std::path::{Path, PathBuf};
+
+/// The uploaded files of a bundle and the file to run.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    entrypoint: String,
+    keep: bool,
+    files: Vec<(String, Bytes)>,
+}
+
+impl Bundle {
+    /// Reads the files from the parts of the multipart body, named by their `filename`.
+    ///
+    /// Rejects file names escaping the bundle directory and a missing entrypoint.
+    pub async fn read(query: &BundleQuery, mut multipart: Multipart) -> Result<Self, String> {
+        let invalid = |e| format!("Invalid multipart body: {e}");
+        let mut files = Vec::new();
+        while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+            let name = field
+                .file_name()
+                .map(String::from)
+                .ok_or("Every part of the bundle must have a filename")?;
+            if contained_path(Path::new(""), &name).is_none() {
+                return Err(format!(
+                    "Bundle file {name:?} must be a relative path inside the bundle"
+                ));
+            }
+            files.push((name, field.bytes().await.map_err(invalid)?));
+        }
+        let bundle = Self {
+            entrypoint: query.entrypoint.clone(),
+            keep: query.keep_bundle,
+            files,
+        };
+        if bundle.entrypoint_file().is_none() {
+            return Err(format!(
+                "Entrypoint {:?} is not in the bundle",
+                bundle.entrypoint
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// The names of the files, relative to the bundle directory.
+    pub fn file_names(&self) -> Vec<String> {
+        self.files.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// The contents of the files, in the order of their names.
+    pub fn contents(&self) -> impl Iterator<Item = &[u8]> {
+        self.files.iter().map(|(_, content)| content.as_ref())
+    }
+
+    /// The content of the entrypoint, e.g. to read its shebang.
+    pub fn entrypoint_script(&self) -> String {
+        self.entrypoint_file()
+            .map(|content| String::from_utf8_lossy(content).into_owned())
+            .unwrap_or_default()
+    }
+
+    fn entrypoint_file(&self) -> Option<&Bytes> {
+        let normalized = |name: &str| Path::new(name).components().collect::<PathBuf>();
+        let entrypoint = normalized(&self.entrypoint);
+        self.files
+            .iter()
+            .find(|(name, _)| normalized(name) == entrypoint)
+            .map(|(_, content)| content)
+    }
+
+    /// The directory of the bundle of the job.
+    pub fn dir(job: &Job) -> PathBuf {
+        working_directory().join(format!("bundle_{}", job.id))
+    }
+
+    /// The path of the entrypoint in the bundle directory `dir`.
+    pub fn entrypoint(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.entrypoint)
+    }
+
+    /// Writes the files to `dir`, making the entrypoint executable if it is run directly.
+    pub async fn write(&self, dir: &Path, executable: bool) -> std::io::Result<()> {
+        tokio::fs::create_dir(dir).await?;
+        for (name, content) in &self.files {
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, content).await?;
+        }
+        #[cfg(unix)]
+        if executable {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = std::fs::Permissions::from_mode(0o755);
+            tokio::fs::set_permissions(self.entrypoint(dir), permissions).await?;
+        }
+        #[cfg(windows)]
+        let _ = executable; // Batch files are executable by their extension.
+        Ok(())
+    }
+
+    /// Removes the bundle directory, unless it should be kept.
+    pub async fn remove(&self, job: &Job, dir: &Path) {
+        if self.keep {
+            log::debug!(job; "keeping bundle {}", dir.display());
+            return;
+        }
+        if let Err(e) = tokio::fs::remove_dir_all(dir).await {
+            log::error!(job; "failed to delete bundle: {e}");
+        }
+    }
+}
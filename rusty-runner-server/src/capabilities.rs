@@ -0,0 +1,149 @@
+//! The capabilities of the server as reported by `GET /api/info`.
+
+use crate::history::JobHistory;
+use crate::identity::UserPolicy;
+use crate::interpreter::Interpreters;
+use crate::library::ScriptLibrary;
+use crate::process::working_directory;
+use crate::routes;
+use crate::sandbox::Sandbox;
+use rusty_runner_api::api::{features, InterpreterInfo, RunnerLimits, ScriptInterpreter};
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How long an interpreter may take to report its version.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The capabilities determined at startup.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    started: Instant,
+    interpreters: Arc<[InterpreterInfo]>,
+    features: Arc<[String]>,
+    limits: RunnerLimits,
+}
+
+impl Capabilities {
+    /// Determines the capabilities of the configuration, running the interpreters to query their versions.
+    pub async fn detect(
+        interpreters: &Interpreters,
+        user_policy: &UserPolicy,
+        sandbox: &Sandbox,
+        history: &JobHistory,
+        library: &ScriptLibrary,
+        max_output_bytes: Option<usize>,
+    ) -> Self {
+        let started = Instant::now();
+
+        let mut infos = Vec::new();
+        for (name, interpreter) in interpreters.iter() {
+            let version = match (name, interpreter.executable()) {
+                (ScriptInterpreter::Cmd, _) => probe_version("cmd", &["/C", "ver"]).await,
+                (_, None) => None,
+                (ScriptInterpreter::Powershell, Some(path)) => {
+                    probe_version(
+                        path,
+                        &[
+                            "-NoProfile",
+                            "-NonInteractive",
+                            "-Command",
+                            "$PSVersionTable.PSVersion.ToString()",
+                        ],
+                    )
+                    .await
+                }
+                (_, Some(path)) => probe_version(path, &["--version"]).await,
+            };
+            infos.push(InterpreterInfo::new(name.clone(), version));
+        }
+
+        let mut supported = vec![
+            features::TRANSCRIPT,
+            features::OUTPUT_FILES,
+            features::IDEMPOTENCY,
+            features::OPENAPI,
+            features::SHEBANG,
+            features::SCRIPT_ARGUMENTS,
+            features::SCRIPT_JSON,
+            features::BUNDLES,
+            features::STRICT_MODE,
+        ];
+        if cfg!(unix) {
+            supported.extend([features::KILL_ORPHANS, features::TTY]);
+        }
+        if cfg!(unix) && user_policy.is_enabled() {
+            supported.push(features::USER_SWITCHING);
+        }
+        if sandbox.is_available().await {
+            supported.push(features::SANDBOX);
+        }
+        if history.is_enabled() {
+            supported.push(features::JOB_HISTORY);
+        }
+        if library.is_enabled() {
+            supported.push(features::SCRIPT_LIBRARY);
+        }
+
+        let mut limits = RunnerLimits::default();
+        limits.max_upload_bytes = Some(routes::MAX_UPLOAD_BYTES as u64);
+        limits.max_output_bytes = max_output_bytes.map(|limit| limit as u64);
+
+        Self {
+            started,
+            interpreters: infos.into(),
+            features: supported.into_iter().map(String::from).collect(),
+            limits,
+        }
+    }
+
+    pub fn interpreters(&self) -> &[InterpreterInfo] {
+        &self.interpreters
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    pub fn limits(&self) -> &RunnerLimits {
+        &self.limits
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Runs the interpreter with the arguments and returns the first line of its output.
+async fn probe_version(program: impl AsRef<OsStr>, arguments: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(arguments)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(VERSION_TIMEOUT, output)
+        .await
+        .ok()?
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+/// The free disk space available to unprivileged users in the working directory.
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // the field types differ between platforms
+pub fn free_disk_bytes() -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(&working_directory()).ok()?;
+    u64::from(stat.blocks_available()).checked_mul(u64::from(stat.fragment_size()))
+}
+
+/// Not supported on windows without `unsafe` system calls.
+#[cfg(windows)]
+pub fn free_disk_bytes() -> Option<u64> {
+    None
+}
@@ -2,14 +2,19 @@
 
 use std::time::{Duration, SystemTime};
 
+use crate::history::JobHistory;
 use crate::process::working_directory;
 
 /// Starts periodic cleanup
 ///
 /// This spawns a background task for each endpoint, running every 8 hours.
-/// Runs [`cleanup_endpoint`].
+/// Runs [`cleanup_endpoint`] and prunes the job `history`.
 #[allow(rustdoc::private_intra_doc_links)] // don't care, mostly for in IDE docs anyway
-pub fn start_cleanup_task(max_age: Option<Duration>, max_size: Option<usize>) {
+pub fn start_cleanup_task(
+    max_age: Option<Duration>,
+    max_size: Option<usize>,
+    history: JobHistory,
+) {
     tokio::spawn(async move {
         // Cleanup runs every 8 hours. This is a tradeoff between resource usage and timely cleanup.
         let mut interval = tokio::time::interval(Duration::from_secs(8 * 60 * 60));
@@ -18,6 +23,9 @@ pub fn start_cleanup_task(max_age: Option<Duration>, max_size: Option<usize>) {
             if let Err(e) = cleanup_endpoint(max_age, max_size).await {
                 log::warn!(e:debug; "cleanup failed");
             }
+            if let Err(e) = history.prune().await {
+                log::warn!(e:debug; "pruning job history failed");
+            }
         }
     });
 }
@@ -0,0 +1,184 @@
+//! The config file given by `--config`, a toml file for settings beyond the command line.
+//!
+//! Every command line option can be set by its name, e.g. `port` or `allowed-users`,
+//! taking precedence over the defaults but not over the command line and environment variables.
+//! Lists are toml arrays, while sizes and durations are strings with the same suffixes as on the command line.
+//! Further settings without a command line counterpart are tables, like the named interpreters.
+//!
+//! # Example
+//! ```toml
+//! host = "0.0.0.0"
+//! port = 8080
+//! bash_path = "/bin/bash"
+//! bash_flags = ["--norc", "--noprofile"]
+//!
+//! # Limits
+//! max_output_bytes = "64MB"
+//! cleanup_max_age = "7d"
+//!
+//! # Policies
+//! allowed_users = ["deploy", "ci"]
+//! sandbox_default = true
+//!
+//! [interpreters.deno]
+//! executable = "deno"
+//! arguments = ["run", "--allow-all", "{script}"]
+//! extension = "ts"
+//!
+//! [interpreters.bash]
+//! executable = "/usr/local/bin/bash"
+//! arguments = ["--noprofile", "--", "{script}"]
+//! strict_arguments = ["--noprofile", "-euo", "pipefail", "--", "{script}"]
+//! extension = "sh"
+//! ```
+
+use crate::interpreter::Interpreter;
+use clap::builder::OsStr;
+use clap::error::{ContextKind, ContextValue};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigFile {
+    /// Named interpreters for `POST /api/runscript`, see [`Interpreter`].
+    #[serde(default)]
+    pub interpreters: BTreeMap<String, Interpreter>,
+    /// The command line options by name, which are validated by [`ConfigFile::apply`].
+    #[serde(flatten)]
+    options: BTreeMap<String, toml::Value>,
+}
+
+impl ConfigFile {
+    /// Reads and parses the config file.
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("Failed to read config file {}: {e}", path.display()))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {e}", path.display()))
+    }
+
+    /// Sets the options of the file as the defaults of the command line `command`,
+    /// such that they are parsed and validated like the command line arguments.
+    ///
+    /// Rejects unknown options and values of the wrong shape.
+    pub fn apply(&self, mut command: clap::Command) -> Result<clap::Command, String> {
+        for (name, value) in &self.options {
+            let id = name.replace('-', "_");
+            let Some(arg) = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id.as_str() && arg.get_long().is_some())
+            else {
+                return Err(format!("Unknown option `{name}`"));
+            };
+            if id == "config" {
+                return Err(String::from(
+                    "Option `config` cannot be set in the config file",
+                ));
+            }
+            let values = match value {
+                toml::Value::Array(values) if arg.get_value_delimiter().is_some() => values
+                    .iter()
+                    .map(|value| scalar(name, value))
+                    .collect::<Result<Vec<_>, _>>()?,
+                toml::Value::Array(_) => {
+                    return Err(format!("Option `{name}` takes a single value, not a list"));
+                }
+                value => vec![scalar(name, value)?],
+            };
+            command = command.mut_arg(id, |arg| arg.default_values(values));
+        }
+        Ok(command)
+    }
+
+    /// The reason of a clap `error` about a value of the file, naming its option,
+    /// or `None` if the error is not about the file, e.g. about the command line.
+    pub fn rejection(&self, command: &clap::Command, error: &clap::Error) -> Option<String> {
+        let (Some(ContextValue::String(arg)), Some(ContextValue::String(value))) = (
+            error.get(ContextKind::InvalidArg),
+            error.get(ContextKind::InvalidValue),
+        ) else {
+            return None;
+        };
+        let (name, _) = self.options.iter().find(|(name, option)| {
+            let id = name.replace('-', "_");
+            let long = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id.as_str())
+                .and_then(clap::Arg::get_long);
+            let values: Vec<&toml::Value> = match option {
+                toml::Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            long.is_some_and(|long| arg.split(' ').next() == Some(&format!("--{long}")))
+                && values
+                    .into_iter()
+                    .any(|option| scalar(name, option).is_ok_and(|option| option == value.as_str()))
+        })?;
+        let reason = if let Some(source) = std::error::Error::source(error) {
+            source.to_string()
+        } else if let Some(ContextValue::Strings(valid)) = error.get(ContextKind::ValidValue) {
+            format!("expected one of {}", valid.join(", "))
+        } else {
+            error.kind().to_string()
+        };
+        Some(format!(
+            "Invalid value {value:?} for option `{name}`: {reason}"
+        ))
+    }
+}
+
+/// The value of an option as it would be given on the command line.
+fn scalar(name: &str, value: &toml::Value) -> Result<OsStr, String> {
+    match value {
+        toml::Value::String(value) => Ok(OsStr::from(value.clone())),
+        toml::Value::Integer(value) => Ok(OsStr::from(value.to_string())),
+        toml::Value::Float(value) => Ok(OsStr::from(value.to_string())),
+        toml::Value::Boolean(value) => Ok(OsStr::from(value.to_string())),
+        _ => Err(format!(
+            "Option `{name}` must be a string, number or boolean"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> clap::Command {
+        clap::Command::new("test")
+            .arg(
+                clap::Arg::new("port")
+                    .long("port")
+                    .value_parser(clap::value_parser!(u16)),
+            )
+            .arg(
+                clap::Arg::new("mode")
+                    .long("mode")
+                    .value_parser(["fast", "slow"]),
+            )
+    }
+
+    fn rejection(config: &str, args: &[&str]) -> Option<String> {
+        let config_file: ConfigFile = toml::from_str(config).expect("valid toml");
+        let error = config_file
+            .apply(command())
+            .expect("known options")
+            .try_get_matches_from(["test"].iter().chain(args))
+            .expect_err("invalid value");
+        config_file.rejection(&command(), &error)
+    }
+
+    #[test]
+    fn rejected_values() {
+        let reason = rejection("port = 70000", &[]).expect("rejected by the file");
+        assert!(reason.contains("\"70000\" for option `port`"), "{reason}");
+        let reason = rejection("mode = \"medium\"", &[]).expect("rejected by the file");
+        assert!(
+            reason.contains("`mode`: expected one of fast, slow"),
+            "{reason}"
+        );
+        // Invalid command line arguments are reported by clap.
+        assert_eq!(rejection("port = 80", &["--port", "70000"]), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Persisting finished jobs, such that their results can be queried later.
+//!
+//! Jobs are appended to a JSON-lines file `jobs.jsonl` in the state directory.
+//! Captured output is capped to [`MAX_STORED_OUTPUT`] bytes per stream,
+//! and old jobs are pruned by the cleanup task according to the [`Retention`].
+
+use rusty_runner_api::api::{JobRecord, JobsQuery, OutputBytes, RunStatus};
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+/// The maximum number of output bytes stored per stream and job.
+const MAX_STORED_OUTPUT: usize = 64 * 1024;
+
+/// The default number of jobs returned by [`JobHistory::query`].
+const DEFAULT_LIMIT: usize = 100;
+
+/// The size of the blocks in which the history is read backwards.
+const READ_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// The job history, which is disabled if no state directory is configured.
+#[derive(Debug, Clone)]
+pub struct JobHistory {
+    path: Option<Arc<Path>>,
+    retention: Retention,
+    /// Serializes access to the file, such that lines don't interleave and pruning doesn't lose jobs.
+    lock: Arc<Mutex<()>>,
+}
+
+/// Which jobs are kept by [`JobHistory::prune`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retention {
+    /// Jobs started longer ago are removed.
+    pub max_age: Option<Duration>,
+    /// Only this many of the most recently recorded jobs are kept.
+    pub max_entries: Option<usize>,
+}
+
+impl JobHistory {
+    pub fn new(state_dir: Option<&Path>, retention: Retention) -> Self {
+        Self {
+            path: state_dir.map(|dir| dir.join("jobs.jsonl").into()),
+            retention,
+            lock: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Appends the job to the history, capping its output.
+    ///
+    /// Errors are only logged, as the job itself already ran.
+    pub async fn record(&self, mut job: JobRecord) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        cap_output(&mut job.status);
+        let mut line = match serde_json::to_vec(&job) {
+            Ok(line) => line,
+            Err(e) => {
+                log::error!(id = job.id; "failed to serialize job: {e}");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().await;
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            file.write_all(&line).await
+        }
+        .await;
+        if let Err(e) = result {
+            log::error!(id = job.id; "failed to record job: {e}");
+        }
+    }
+
+    /// Returns the jobs matching the query, most recently recorded first.
+    ///
+    /// Reading stops as soon as the limit is reached.
+    pub async fn query(&self, query: &JobsQuery) -> std::io::Result<Vec<JobRecord>> {
+        let since = query
+            .since
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        let mut jobs = Vec::new();
+        if limit == 0 {
+            return Ok(jobs);
+        }
+        self.read_newest_first(|job| {
+            if since.is_none_or(|since| job.started_at >= since)
+                && query.status.is_none_or(|status| job.job_status() == status)
+                && query
+                    .job_id
+                    .as_deref()
+                    .is_none_or(|job_id| job.job_id() == Some(job_id))
+            {
+                jobs.push(job);
+            }
+            jobs.len() < limit
+        })
+        .await?;
+        Ok(jobs)
+    }
+
+    /// Returns the job with the given id, if recorded.
+    pub async fn get(&self, id: u64) -> std::io::Result<Option<JobRecord>> {
+        let mut found = None;
+        self.read_newest_first(|job| {
+            if job.id == id {
+                found = Some(job);
+            }
+            found.is_none()
+        })
+        .await?;
+        Ok(found)
+    }
+
+    /// Removes the jobs exceeding the [`Retention`], rewriting the file if any are removed.
+    pub async fn prune(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.retention.max_age.is_none() && self.retention.max_entries.is_none() {
+            return Ok(());
+        }
+        let _guard = self.lock.lock().await;
+        let content = match tokio::fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let now = SystemTime::now();
+        let mut lines: Vec<&str> = content
+            .lines()
+            .filter(|line| {
+                parse(line.as_bytes()).is_some_and(|job| {
+                    self.retention.max_age.is_none_or(|max_age| {
+                        now.duration_since(job.started_at)
+                            .is_ok_and(|age| age <= max_age)
+                    })
+                })
+            })
+            .collect();
+        if let Some(max_entries) = self.retention.max_entries {
+            lines.drain(..lines.len().saturating_sub(max_entries));
+        }
+        let removed = content.lines().count() - lines.len();
+        if removed == 0 {
+            return Ok(());
+        }
+
+        // Replace the file atomically, such that jobs aren't lost if writing fails.
+        let mut pruned = lines.join("\n");
+        if !pruned.is_empty() {
+            pruned.push('\n');
+        }
+        let temporary = path.with_extension("jsonl.tmp");
+        tokio::fs::write(&temporary, pruned).await?;
+        tokio::fs::rename(&temporary, path).await?;
+        log::info!(removed; "pruned job history");
+        Ok(())
+    }
+
+    /// Reads the jobs backwards, in the reverse order they were recorded, until `visit` returns `false`.
+    ///
+    /// Malformed lines are skipped.
+    async fn read_newest_first(
+        &self,
+        mut visit: impl FnMut(JobRecord) -> bool,
+    ) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _guard = self.lock.lock().await;
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut end = file.metadata().await?.len();
+        // The bytes from `end` up to the first line break, i.e. the beginning of a line.
+        let mut rest = Vec::new();
+        while end > 0 {
+            let start = end.saturating_sub(READ_BLOCK_SIZE);
+            #[allow(clippy::cast_possible_truncation)] // at most the block size
+            let mut block = vec![0; (end - start) as usize];
+            file.seek(SeekFrom::Start(start)).await?;
+            file.read_exact(&mut block).await?;
+            block.append(&mut rest);
+            end = start;
+
+            while let Some(position) = block.iter().rposition(|&byte| byte == b'\n') {
+                let line = block.split_off(position + 1);
+                block.truncate(position);
+                if parse(&line).is_some_and(|job| !visit(job)) {
+                    return Ok(());
+                }
+            }
+            rest = block;
+        }
+        if let Some(job) = parse(&rest) {
+            visit(job);
+        }
+        Ok(())
+    }
+}
+
+/// Parses a line of the history, logging malformed ones.
+fn parse(line: &[u8]) -> Option<JobRecord> {
+    if line.trim_ascii().is_empty() {
+        return None;
+    }
+    serde_json::from_slice(line)
+        .inspect_err(|e| log::warn!("skipping malformed job record: {e}"))
+        .ok()
+}
+
+/// Truncates the stored output to [`MAX_STORED_OUTPUT`] bytes per stream,
+/// updating the total byte counts accordingly.
+fn cap_output(status: &mut RunStatus) {
+    let RunStatus::Completed {
+        stdout,
+        stderr,
+        stdout_total_bytes,
+        stderr_total_bytes,
+        transcript,
+        ..
+    } = status
+    else {
+        return;
+    };
+    cap_stream(stdout, stdout_total_bytes);
+    cap_stream(stderr, stderr_total_bytes);
+    if let Some(transcript) = transcript {
+        let mut stored = 0;
+        transcript.retain(|chunk| {
+            stored += chunk.data.len();
+            stored <= 2 * MAX_STORED_OUTPUT
+        });
+    }
+}
+
+fn cap_stream(output: &mut Option<OutputBytes>, total_bytes: &mut Option<u64>) {
+    let Some(bytes) = output
+        .as_mut()
+        .filter(|bytes| bytes.len() > MAX_STORED_OUTPUT)
+    else {
+        return;
+    };
+    total_bytes.get_or_insert(bytes.len() as u64);
+    *bytes = OutputBytes::new(bytes[..MAX_STORED_OUTPUT].to_vec(), bytes.encoding());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_runner_api::api::{JobRequest, RunRequest};
+
+    fn history(name: &str, retention: Retention) -> JobHistory {
+        let dir =
+            std::env::temp_dir().join(format!("rusty-runner-history-{name}-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        JobHistory::new(Some(&dir), retention)
+    }
+
+    fn remove(history: JobHistory) {
+        let path = history.path.expect("history is enabled");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn job(id: u64, started_at: SystemTime, output: usize) -> JobRecord {
+        let request = RunRequest::new("echo").job_id(format!("job-{}", id % 2));
+        let status = RunStatus::Failure {
+            reason: "x".repeat(output),
+        };
+        JobRecord::new(id, started_at, JobRequest::Run(request), status)
+    }
+
+    #[tokio::test]
+    async fn reads_newest_first_across_blocks() {
+        let history = history("blocks", Retention::default());
+        // Lines larger than a block are read across several of them.
+        for id in 0..20 {
+            history.record(job(id, SystemTime::now(), 10_000)).await;
+        }
+        history.record(job(20, SystemTime::now(), 100_000)).await;
+        history.record(job(21, SystemTime::now(), 10)).await;
+
+        let jobs = history.query(&JobsQuery::default()).await.unwrap();
+        let ids: Vec<_> = jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, (0..22).rev().collect::<Vec<_>>());
+
+        let mut query = JobsQuery::default();
+        query.job_id = Some(String::from("job-1"));
+        query.limit = Some(3);
+        let jobs = history.query(&query).await.unwrap();
+        let ids: Vec<_> = jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, [21, 19, 17]);
+
+        assert_eq!(history.get(0).await.unwrap().map(|job| job.id), Some(0));
+        assert_eq!(history.get(20).await.unwrap().map(|job| job.id), Some(20));
+        assert!(history.get(22).await.unwrap().is_none());
+        remove(history);
+    }
+
+    #[tokio::test]
+    async fn prunes_by_age_and_entries() {
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(1000)),
+            max_entries: Some(2),
+        };
+        let history = history("prune", retention);
+        let old = SystemTime::now() - Duration::from_secs(2000);
+        history.record(job(0, SystemTime::now(), 10)).await;
+        history.record(job(1, old, 10)).await;
+        history.record(job(2, SystemTime::now(), 10)).await;
+        history.record(job(3, SystemTime::now(), 10)).await;
+
+        history.prune().await.unwrap();
+        let jobs = history.query(&JobsQuery::default()).await.unwrap();
+        let ids: Vec<_> = jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, [3, 2]);
+        remove(history);
+    }
+}
@@ -0,0 +1,137 @@
+//! Deduplicating retried requests by their `Idempotency-Key` header.
+//!
+//! The first request with a key runs the job, retries with the same key within the retention window
+//! receive its response, waiting for it if the job is still running.
+//! Reusing a key for a different request is rejected.
+//!
+//! Keyed jobs are spawned, such that they complete even if the client disconnects and a retry can attach to them.
+//! Hence, unlike other jobs, they are not killed along with their process group when the request is cancelled,
+//! but only kill their orphans once they complete, if requested by `kill_orphans`.
+
+use crate::job::Job;
+use axum::http::{HeaderMap, StatusCode};
+use rusty_runner_api::api::RunResponse;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// The header carrying the client chosen key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The http status and response of a job.
+pub type Outcome = (StatusCode, RunResponse);
+
+/// The recently used keys and the outcomes of their jobs.
+#[derive(Debug, Clone)]
+pub struct Idempotency {
+    retention: Duration,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    created: Instant,
+    /// The [`fingerprint`] of the original request.
+    fingerprint: u64,
+    /// The original job, which retries attach to.
+    job: Job,
+    /// `None` while the job is still running.
+    outcome: watch::Receiver<Option<Outcome>>,
+}
+
+/// Hashes a `request` along with the raw `data` sent with it, e.g. the script,
+/// such that retries can be told apart from different requests reusing a key.
+pub fn fingerprint<'a>(request: &impl Serialize, data: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // As a json value, maps like the labels are sorted by their keys.
+    serde_json::to_value(request)
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    for data in data {
+        data.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+impl Idempotency {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Runs the `job`, unless a job with the same key was already started.
+    /// In that case, returns the outcome of the original job instead.
+    ///
+    /// Keyed jobs are spawned, such that they complete even if the client disconnects.
+    /// Keys are scoped by the `endpoint`, such that different endpoints cannot collide,
+    /// and bound to the [`fingerprint`] of the request.
+    pub async fn run(
+        &self,
+        headers: &HeaderMap,
+        endpoint: &str,
+        fingerprint: u64,
+        job: Job,
+        execute: impl Future<Output = Outcome> + Send + 'static,
+    ) -> Outcome {
+        let Some(key) = headers
+            .get(IDEMPOTENCY_KEY)
+            .and_then(|key| key.to_str().ok())
+            .filter(|key| !key.is_empty())
+        else {
+            return execute.await;
+        };
+        let key = format!("{endpoint}:{key}");
+
+        let (job, mut outcome) = {
+            let mut entries = self.entries.lock().expect("Idempotency lock poisoned");
+            // Forget expired keys, but never those of running jobs.
+            entries.retain(|_, entry| {
+                let running =
+                    entry.outcome.borrow().is_none() && entry.outcome.has_changed().is_ok();
+                running || entry.created.elapsed() < self.retention
+            });
+            if let Some(entry) = entries.get(&key) {
+                if entry.fingerprint != fingerprint {
+                    log::warn!(job, key:display = key; "rejected idempotency key reused for a different request");
+                    let reason = "Idempotency key was already used for a different request";
+                    return (StatusCode::UNPROCESSABLE_ENTITY, job.failure(reason));
+                }
+                log::info!(job = entry.job, key:display = key; "replaying idempotent request");
+                (entry.job.clone(), entry.outcome.clone())
+            } else {
+                let (sender, receiver) = watch::channel(None);
+                entries.insert(
+                    key,
+                    Entry {
+                        created: Instant::now(),
+                        fingerprint,
+                        job: job.clone(),
+                        outcome: receiver.clone(),
+                    },
+                );
+                tokio::spawn(async move {
+                    sender.send_replace(Some(execute.await));
+                });
+                (job, receiver)
+            }
+        };
+
+        let result = outcome.wait_for(Option::is_some).await;
+        if let Ok(Some(outcome)) = result.as_deref() {
+            return outcome.clone();
+        }
+        // The job panicked before sending its outcome.
+        log::error!(job; "idempotent job ended without a response");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            job.failure("Job ended without a response"),
+        )
+    }
+}
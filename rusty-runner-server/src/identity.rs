@@ -0,0 +1,181 @@
+//! Running commands as a different unix user or group.
+//!
+//! Only users and groups on the server side allowlist may be used.
+//! Switching requires the server to have the privileges to do so, commonly by running as root.
+
+use std::path::Path;
+use std::sync::Arc;
+use tokio::process::Command;
+
+/// The allowlist of users and groups that commands may be run as.
+#[derive(Debug, Clone)]
+#[cfg_attr(windows, allow(dead_code))]
+pub struct UserPolicy {
+    allowed_users: Arc<[String]>,
+    allowed_groups: Arc<[String]>,
+    /// The [launcher](crate::launch), resolved at startup if switching is enabled.
+    launcher: Option<Arc<Path>>,
+}
+
+/// A user and group resolved by [`UserPolicy::resolve`], which the command is switched to by [`Identity::apply`].
+#[derive(Debug, Clone)]
+#[cfg_attr(windows, allow(dead_code))]
+pub struct Identity {
+    /// The user id, or `None` to keep the user of the server.
+    pub uid: Option<u32>,
+    pub gid: u32,
+    /// The supplementary groups, which replace the ones of the server.
+    pub groups: Vec<u32>,
+    /// `HOME` and `USER` of the user.
+    environment: Vec<(&'static str, String)>,
+    /// The launcher setting the supplementary groups, if `std` cannot.
+    launcher: Option<Arc<Path>>,
+}
+
+impl Identity {
+    /// Switches the user and group of the command and sets `HOME` and `USER` to match the user.
+    ///
+    /// Must be called before configuring the stdio of the command.
+    pub fn apply(&self, mut command: Command) -> Command {
+        command.envs(self.environment.iter().map(|(key, value)| (key, value)));
+        #[cfg(unix)]
+        {
+            if let Some(launcher) = &self.launcher {
+                return crate::launch::wrap(&command, launcher, self);
+            }
+            if let Some(uid) = self.uid {
+                command.uid(uid);
+            }
+            command.gid(self.gid);
+        }
+        command
+    }
+}
+
+impl UserPolicy {
+    /// Creates the policy, resolving the launcher if any user or group is allowed.
+    pub fn new(allowed_users: Vec<String>, allowed_groups: Vec<String>) -> Result<Self, String> {
+        #[cfg(unix)]
+        let launcher = if allowed_users.is_empty() && allowed_groups.is_empty() {
+            None
+        } else {
+            let launcher = crate::launch::resolve_launcher()
+                .map_err(|e| format!("Failed to find the launcher: {e}"))?;
+            Some(launcher.into())
+        };
+        #[cfg(windows)]
+        let launcher = None;
+        Ok(Self {
+            allowed_users: allowed_users.into(),
+            allowed_groups: allowed_groups.into(),
+            launcher,
+        })
+    }
+
+    /// Whether any user or group may be switched to.
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_users.is_empty() || !self.allowed_groups.is_empty()
+    }
+
+    /// Resolves the identity to run as `user` and/or `group`, if they are allowed.
+    ///
+    /// If only the user is given, its primary group is used.
+    /// The supplementary groups are the ones of the user in the group database,
+    /// or just `group` if only that is given.
+    #[cfg(unix)]
+    pub fn resolve(
+        &self,
+        user: Option<&str>,
+        group: Option<&str>,
+    ) -> Result<Option<Identity>, String> {
+        use nix::unistd::{Group, User};
+
+        let user = user
+            .map(|name| {
+                if !self.allowed_users.iter().any(|allowed| allowed == name) {
+                    return Err(format!("User {name} not allowed"));
+                }
+                User::from_name(name)
+                    .map_err(|e| format!("Failed to look up user {name}: {e}"))?
+                    .ok_or_else(|| format!("Unknown user {name}"))
+            })
+            .transpose()?;
+        let group = group
+            .map(|name| {
+                if !self.allowed_groups.iter().any(|allowed| allowed == name) {
+                    return Err(format!("Group {name} not allowed"));
+                }
+                Group::from_name(name)
+                    .map_err(|e| format!("Failed to look up group {name}: {e}"))?
+                    .ok_or_else(|| format!("Unknown group {name}"))
+            })
+            .transpose()?;
+
+        let mut identity = match (user, group) {
+            (None, None) => return Ok(None),
+            (None, Some(group)) => Identity {
+                uid: None,
+                gid: group.gid.as_raw(),
+                groups: vec![group.gid.as_raw()],
+                environment: Vec::new(),
+                launcher: None,
+            },
+            (Some(user), group) => {
+                let mut groups = supplementary_groups(&user)?;
+                let gid = group.map_or(user.gid, |group| group.gid).as_raw();
+                if !groups.contains(&gid) {
+                    groups.push(gid);
+                }
+                Identity {
+                    uid: Some(user.uid.as_raw()),
+                    gid,
+                    groups,
+                    environment: vec![
+                        ("HOME", user.dir.to_string_lossy().into_owned()),
+                        ("USER", user.name),
+                    ],
+                    launcher: None,
+                }
+            }
+        };
+        // When switching the user, `std` clears the supplementary groups, which only suffices
+        // if they are just the primary group. Otherwise, it would keep the ones of the server.
+        let primary_only = identity.groups.iter().all(|&group| group == identity.gid);
+        if identity.uid.is_none() || !primary_only {
+            identity.launcher.clone_from(&self.launcher);
+        }
+        Ok(Some(identity))
+    }
+
+    /// Switching users is not supported on windows.
+    #[cfg(windows)]
+    #[allow(clippy::unused_self)]
+    pub fn resolve(
+        &self,
+        user: Option<&str>,
+        group: Option<&str>,
+    ) -> Result<Option<Identity>, String> {
+        if user.is_some() || group.is_some() {
+            Err(String::from("Switching users is not supported on windows"))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The groups of `user` in the group database, including its primary group.
+#[cfg(all(unix, not(target_vendor = "apple")))]
+fn supplementary_groups(user: &nix::unistd::User) -> Result<Vec<u32>, String> {
+    let name = std::ffi::CString::new(user.name.as_str())
+        .map_err(|_| format!("Invalid user name {}", user.name))?;
+    let groups = nix::unistd::getgrouplist(&name, user.gid)
+        .map_err(|e| format!("Failed to look up groups of user {}: {e}", user.name))?;
+    Ok(groups.into_iter().map(nix::unistd::Gid::as_raw).collect())
+}
+
+/// Apple platforms have no `getgrouplist` in [`nix`], so only the primary group is kept.
+#[cfg(target_vendor = "apple")]
+#[allow(clippy::unnecessary_wraps)]
+fn supplementary_groups(user: &nix::unistd::User) -> Result<Vec<u32>, String> {
+    Ok(vec![user.gid.as_raw()])
+}
@@ -0,0 +1,347 @@
+//! The interpreters configured for `POST /api/runscript`.
+//!
+//! Besides the builtin [`ScriptInterpreter`]s with a path in the `CliArgs`,
+//! operators may declare named interpreters in the [config file](crate::config).
+
+use rusty_runner_api::api::ScriptInterpreter;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The placeholder for the script path in [`Interpreter`] templates.
+pub const SCRIPT_PLACEHOLDER: &str = "{script}";
+
+/// Makes PowerShell stop at the first error and on uninitialized variables.
+const POWERSHELL_STRICT: &str = "$ErrorActionPreference = 'Stop'; Set-StrictMode -Version Latest;";
+
+/// How to run a script file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Interpreter {
+    /// The path of the interpreter, or just its name if it is in the PATH.
+    /// The script itself is run if it is the [`SCRIPT_PLACEHOLDER`].
+    executable: PathBuf,
+    /// The arguments, where [`SCRIPT_PLACEHOLDER`] is replaced by the script path.
+    #[serde(default = "script_only")]
+    arguments: Vec<String>,
+    /// The arguments in `strict` mode, which is not supported if `None`.
+    #[serde(default)]
+    strict_arguments: Option<Vec<String>>,
+    /// PowerShell has no strict flags, so the script is called by a `-Command` setting the preferences.
+    #[serde(skip)]
+    powershell_strict: bool,
+    /// The extension of the script file, without a leading dot.
+    extension: String,
+}
+
+fn script_only() -> Vec<String> {
+    vec![String::from(SCRIPT_PLACEHOLDER)]
+}
+
+impl Interpreter {
+    /// The builtin invocation of the interpreter at `path`, passing the `flags` before the script.
+    pub fn builtin(interpreter: &ScriptInterpreter, path: PathBuf, flags: &[String]) -> Self {
+        let (arguments, strict): (&[&str], Option<&[&str]>) = match interpreter {
+            // `bash -- {file}`, likewise for the other shells.
+            ScriptInterpreter::Bash | ScriptInterpreter::Zsh => (
+                &["--", SCRIPT_PLACEHOLDER],
+                Some(&["-e", "-u", "-o", "pipefail", "--", SCRIPT_PLACEHOLDER]),
+            ),
+            // POSIX sh has no `pipefail`.
+            ScriptInterpreter::Sh => (
+                &["--", SCRIPT_PLACEHOLDER],
+                Some(&["-e", "-u", "--", SCRIPT_PLACEHOLDER]),
+            ),
+            // `powershell -File {file}`.
+            ScriptInterpreter::Powershell => (&["-File", SCRIPT_PLACEHOLDER], None),
+            ScriptInterpreter::Perl => (
+                &[SCRIPT_PLACEHOLDER],
+                Some(&["-Mstrict", "-Mwarnings=FATAL,all", SCRIPT_PLACEHOLDER]),
+            ),
+            // `python {file}`, the script path is absolute and cannot be mistaken for an option.
+            _ => (&[SCRIPT_PLACEHOLDER], None),
+        };
+        let with_flags = |arguments: &[&str]| {
+            flags
+                .iter()
+                .filter(|flag| !flag.is_empty())
+                .cloned()
+                .chain(arguments.iter().copied().map(String::from))
+                .collect()
+        };
+        Self {
+            executable: path,
+            arguments: with_flags(arguments),
+            strict_arguments: strict.map(with_flags),
+            powershell_strict: *interpreter == ScriptInterpreter::Powershell,
+            extension: String::from(interpreter.as_extension()),
+        }
+    }
+
+    /// Cmd runs batch files directly.
+    fn cmd() -> Self {
+        Self::direct(ScriptInterpreter::Cmd.as_extension())
+    }
+
+    /// Runs the script itself, which must be executable.
+    fn direct(extension: &str) -> Self {
+        Self {
+            executable: PathBuf::from(SCRIPT_PLACEHOLDER),
+            arguments: Vec::new(),
+            strict_arguments: None,
+            powershell_strict: false,
+            extension: String::from(extension),
+        }
+    }
+
+    /// Runs the script by the interpreter of its `shebang` like the kernel would,
+    /// i.e. passing the rest of the line as a single argument before the script.
+    /// Unlike running the script itself, it needs not be executable,
+    /// e.g. if the working directory is on a `noexec` mount.
+    fn shebang(shebang: &str) -> Result<Self, String> {
+        let (executable, argument) = shebang
+            .split_once([' ', '\t'])
+            .map_or((shebang, ""), |(executable, argument)| {
+                (executable, argument.trim())
+            });
+        if executable.is_empty() {
+            return Err(String::from("Script has an empty shebang (`#!`)"));
+        }
+        let arguments = Some(argument)
+            .filter(|argument| !argument.is_empty())
+            .into_iter()
+            .chain([SCRIPT_PLACEHOLDER])
+            .map(String::from)
+            .collect();
+        Ok(Self {
+            executable: PathBuf::from(executable),
+            arguments,
+            strict_arguments: None,
+            powershell_strict: false,
+            extension: String::new(),
+        })
+    }
+
+    /// The executable, unless the script is run directly.
+    pub fn executable(&self) -> Option<&Path> {
+        (self.executable != Path::new(SCRIPT_PLACEHOLDER)).then_some(&self.executable)
+    }
+
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// The program and arguments to run the script at `script_path` with the script `arguments`,
+    /// or `None` if `strict` mode is not supported by this interpreter.
+    pub fn invocation(
+        &self,
+        script_path: &Path,
+        arguments: &[String],
+        strict: bool,
+    ) -> Option<(OsString, Vec<OsString>)> {
+        let program = self
+            .executable()
+            .unwrap_or(script_path)
+            .as_os_str()
+            .to_owned();
+        if strict && self.powershell_strict {
+            return Some((program, self.powershell_command(script_path, arguments)));
+        }
+        let template = if strict {
+            self.strict_arguments.as_ref()?
+        } else {
+            &self.arguments
+        };
+        let invocation = template
+            .iter()
+            .map(|argument| {
+                if argument == SCRIPT_PLACEHOLDER {
+                    script_path.as_os_str().to_owned()
+                } else {
+                    OsString::from(argument)
+                }
+            })
+            .chain(arguments.iter().map(OsString::from))
+            .collect();
+        Some((program, invocation))
+    }
+
+    /// `powershell {flags} -Command "{strict} & '{file}' '{arguments}'; exit $LASTEXITCODE"`,
+    /// where the flags are the arguments before `-File`.
+    fn powershell_command(&self, script_path: &Path, arguments: &[String]) -> Vec<OsString> {
+        let mut command = format!(
+            "{POWERSHELL_STRICT} & {}",
+            powershell_quote(&script_path.to_string_lossy())
+        );
+        for argument in arguments {
+            command.push(' ');
+            command.push_str(&powershell_quote(argument));
+        }
+        command.push_str("; exit $LASTEXITCODE");
+        self.arguments
+            .iter()
+            .take_while(|argument| *argument != "-File")
+            .map(OsString::from)
+            .chain([OsString::from("-Command"), OsString::from(command)])
+            .collect()
+    }
+}
+
+/// The configured interpreters by name.
+#[derive(Debug, Clone)]
+pub struct Interpreters {
+    interpreters: Arc<[(ScriptInterpreter, Interpreter)]>,
+}
+
+impl Interpreters {
+    /// Configures the builtin interpreters with a path, skipping those without,
+    /// and the named interpreters, which take precedence over builtins of the same name.
+    pub fn new(
+        paths: impl IntoIterator<Item = (ScriptInterpreter, Option<PathBuf>)>,
+        flags: &[(ScriptInterpreter, Vec<String>)],
+        named: BTreeMap<String, Interpreter>,
+    ) -> Result<Self, String> {
+        let mut interpreters: Vec<_> = paths
+            .into_iter()
+            .filter_map(|(interpreter, path)| {
+                let flags = flags
+                    .iter()
+                    .find(|(flagged, _)| *flagged == interpreter)
+                    .map_or(&[][..], |(_, flags)| flags);
+                let builtin = Interpreter::builtin(&interpreter, path?, flags);
+                Some((interpreter, builtin))
+            })
+            .collect();
+        if cfg!(windows) {
+            interpreters.push((ScriptInterpreter::Cmd, Interpreter::cmd()));
+        }
+        for (name, interpreter) in named {
+            let name = name
+                .parse()
+                .map_err(|e| format!("Interpreter `{name}`: {e}"))?;
+            if name == ScriptInterpreter::Auto {
+                return Err(format!("Interpreter `{name}`: the name is reserved"));
+            }
+            if interpreter.extension.is_empty()
+                || !interpreter
+                    .extension
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric())
+            {
+                return Err(format!(
+                    "Interpreter `{name}`: invalid extension `{}`",
+                    interpreter.extension
+                ));
+            }
+            let templates = [
+                Some(&interpreter.arguments),
+                interpreter.strict_arguments.as_ref(),
+            ];
+            if interpreter.executable().is_some()
+                && !templates
+                    .into_iter()
+                    .flatten()
+                    .all(|template| template.iter().any(|a| a == SCRIPT_PLACEHOLDER))
+            {
+                return Err(format!(
+                    "Interpreter `{name}`: the arguments must contain `{SCRIPT_PLACEHOLDER}`"
+                ));
+            }
+            interpreters.retain(|(configured, _)| *configured != name);
+            interpreters.push((name, interpreter));
+        }
+        Ok(Self {
+            interpreters: interpreters.into(),
+        })
+    }
+
+    /// The interpreter, if configured.
+    pub fn get(&self, interpreter: &ScriptInterpreter) -> Option<&Interpreter> {
+        self.iter()
+            .find(|(configured, _)| *configured == interpreter)
+            .map(|(_, configured)| configured)
+    }
+
+    /// The interpreter to run the script with, which is determined by its shebang for [`ScriptInterpreter::Auto`].
+    ///
+    /// On unix, such scripts are run by the interpreter of the shebang, see [`Interpreter::shebang`],
+    /// while on windows the shebang is resolved by [`Interpreters::by_shebang`].
+    pub fn resolve(
+        &self,
+        interpreter: &ScriptInterpreter,
+        script: &str,
+    ) -> Result<Cow<'_, Interpreter>, String> {
+        if *interpreter != ScriptInterpreter::Auto {
+            return self
+                .get(interpreter)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| String::from("Interpreter not supported"));
+        }
+        let shebang =
+            shebang(script).ok_or("Script has no shebang (`#!`) for interpreter `auto`")?;
+        if cfg!(unix) {
+            Interpreter::shebang(shebang).map(Cow::Owned)
+        } else {
+            self.by_shebang(shebang).map(Cow::Borrowed)
+        }
+    }
+
+    /// The configured interpreter named by the shebang, ignoring its directory and a trailing version.
+    /// E.g. `/usr/bin/env python3` resolves the `python` interpreter, unless one is named `python3`.
+    fn by_shebang(&self, shebang: &str) -> Result<&Interpreter, String> {
+        let mut words = shebang.split_whitespace();
+        let mut program = words.next().unwrap_or_default();
+        if file_stem(program) == "env" {
+            program = words
+                .find(|word| !word.starts_with('-'))
+                .unwrap_or_default();
+        }
+        let name = file_stem(program);
+        let unversioned = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        [name, unversioned]
+            .into_iter()
+            .filter_map(|name| name.parse().ok())
+            .find_map(|name| self.get(&name))
+            .ok_or_else(|| format!("Shebang interpreter `{name}` not supported"))
+    }
+
+    /// The configured interpreters.
+    pub fn iter(&self) -> impl Iterator<Item = (&ScriptInterpreter, &Interpreter)> {
+        self.interpreters
+            .iter()
+            .map(|(name, interpreter)| (name, interpreter))
+    }
+}
+
+/// The shebang line of the script without the leading `#!`, if any.
+fn shebang(script: &str) -> Option<&str> {
+    let line = script
+        .strip_prefix("#!")?
+        .lines()
+        .next()
+        .unwrap_or_default();
+    Some(line.trim())
+}
+
+/// The file name of the path without an extension, e.g. `python` for `C:\Python\python.exe`.
+fn file_stem(path: &str) -> &str {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    name.strip_suffix(".exe").unwrap_or(name)
+}
+
+/// Quotes the argument as a verbatim PowerShell string, in which only single quotes are special.
+/// PowerShell also accepts the typographic single quotes, which are escaped likewise by doubling.
+fn powershell_quote(argument: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in argument.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
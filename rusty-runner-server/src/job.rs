@@ -0,0 +1,109 @@
+//! The identity of a job, which is attached to its response and log records.
+
+use rusty_runner_api::api::{RunResponse, RunStatus};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The runner assigned id and the client chosen id and labels of a job.
+///
+/// Logged as a single `job` key-value, e.g. `job=73001 [deploy-42] {pipeline=ci, sha=abc123}`.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: u64,
+    #[allow(clippy::struct_field_names)] // named as in the api
+    pub job_id: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+impl Job {
+    /// A job with a new random id.
+    pub fn new(job_id: Option<String>, labels: HashMap<String, String>) -> Self {
+        Self {
+            id: fastrand::u64(..),
+            job_id,
+            labels,
+        }
+    }
+
+    /// The response of this job with the given status.
+    pub fn response(&self, status: RunStatus) -> RunResponse {
+        let mut response = RunResponse::new(self.id, status);
+        response.job_id.clone_from(&self.job_id);
+        response.labels.clone_from(&self.labels);
+        response
+    }
+
+    /// The response of this job failing for the given reason.
+    pub fn failure(&self, reason: impl Into<String>) -> RunResponse {
+        self.response(RunStatus::Failure {
+            reason: reason.into(),
+        })
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(job_id) = &self.job_id {
+            write!(f, " [{job_id}]")?;
+        }
+        if !self.labels.is_empty() {
+            // Sorted for readable logs.
+            let labels: BTreeMap<_, _> = self.labels.iter().collect();
+            write!(f, " {{")?;
+            for (i, (key, value)) in labels.into_iter().enumerate() {
+                let separator = if i == 0 { "" } else { ", " };
+                write!(f, "{separator}{key}={value}")?;
+            }
+            write!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+impl log::kv::ToValue for Job {
+    fn to_value(&self) -> log::kv::Value<'_> {
+        log::kv::Value::from_display(self)
+    }
+}
+
+/// The client chosen ids of the running jobs.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveJobs(Arc<Mutex<HashSet<String>>>);
+
+impl ActiveJobs {
+    /// Marks the `job_id` of the job as active until the guard is dropped.
+    ///
+    /// Fails if another job with the same `job_id` is still running.
+    pub fn register(&self, job: &Job) -> Result<ActiveJob, String> {
+        if let Some(job_id) = &job.job_id {
+            let mut active = self.0.lock().expect("Active jobs lock poisoned");
+            if !active.insert(job_id.clone()) {
+                return Err(format!("Job {job_id:?} is already running"));
+            }
+        }
+        Ok(ActiveJob {
+            jobs: self.clone(),
+            job_id: job.job_id.clone(),
+        })
+    }
+}
+
+/// Keeps a `job_id` active, see [`ActiveJobs::register`].
+pub struct ActiveJob {
+    jobs: ActiveJobs,
+    job_id: Option<String>,
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        if let Some(job_id) = &self.job_id {
+            self.jobs
+                .0
+                .lock()
+                .expect("Active jobs lock poisoned")
+                .remove(job_id);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Launching commands through the server binary itself, for process setup between fork and exec.
+//!
+//! The user and group are switched by [`CommandExt::uid`] and [`CommandExt::gid`], but `std` can only
+//! clear the supplementary groups, and setting them otherwise requires an `unsafe` pre-exec hook.
+//! Identities with further supplementary groups therefore run the server binary as the launcher
+//! `rusty-runner-server --launch <options> -- <program> <arguments>`,
+//! which sets up the process with safe calls and then replaces itself by the program.
+//! This mode is internal to the server and not part of its command line interface.
+
+use crate::identity::Identity;
+use nix::unistd::{Gid, Uid};
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::process::Command;
+
+/// The first argument selecting the launcher instead of the server.
+const LAUNCH_ARG: &str = "--launch";
+
+/// Resolves the server binary to run as the launcher, once at startup.
+///
+/// On linux this is the running binary itself, which stays available even if it is replaced
+/// or deleted, e.g. during an upgrade. Elsewhere it is the path the server was started from.
+pub fn resolve_launcher() -> std::io::Result<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        let exe = Path::new("/proc/self/exe");
+        if exe.exists() {
+            return Ok(exe.to_path_buf());
+        }
+    }
+    std::env::current_exe()
+}
+
+/// Wraps `command` to run through the `launcher` as `identity`, keeping its environment and directory.
+///
+/// Must be called before configuring the stdio of the command, which is not carried over.
+pub fn wrap(command: &Command, launcher: &Path, identity: &Identity) -> Command {
+    let inner = command.as_std();
+    let mut launch = Command::new(launcher);
+    launch.arg(LAUNCH_ARG);
+    if let Some(uid) = identity.uid {
+        launch.arg("--uid").arg(uid.to_string());
+    }
+    launch.arg("--gid").arg(identity.gid.to_string());
+    let groups: Vec<_> = identity.groups.iter().map(u32::to_string).collect();
+    launch.arg("--groups").arg(groups.join(","));
+    launch
+        .arg("--")
+        .arg(inner.get_program())
+        .args(inner.get_args());
+    for (key, value) in inner.get_envs() {
+        match value {
+            Some(value) => launch.env(key, value),
+            None => launch.env_remove(key),
+        };
+    }
+    if let Some(dir) = inner.get_current_dir() {
+        launch.current_dir(dir);
+    }
+    launch
+}
+
+/// Runs the launcher if the server binary was started as one, otherwise returns `None`.
+///
+/// Only returns if launching failed, as the launcher is replaced by the program.
+pub fn launch_if_requested() -> Option<ExitCode> {
+    let mut args = std::env::args_os().skip(1);
+    if args.next()? != LAUNCH_ARG {
+        return None;
+    }
+    let Err(reason) = launch(args);
+    eprintln!("rusty-runner-server launcher: {reason}");
+    // Like a shell, report commands that could not be run by 127.
+    Some(ExitCode::from(127))
+}
+
+/// Sets up the process as given by the arguments and executes the program,
+/// returning the reason if that fails.
+fn launch(args: impl Iterator<Item = OsString>) -> Result<Infallible, String> {
+    use nix::unistd::{setgid, setuid};
+
+    let setup = Setup::parse(args)?;
+    // The groups must be set before dropping the privileges by switching the user.
+    if let Some(groups) = &setup.groups {
+        set_groups(groups)?;
+    }
+    if let Some(gid) = setup.gid {
+        setgid(gid).map_err(|e| format!("Failed to set group {gid}: {e}"))?;
+    }
+    if let Some(uid) = setup.uid {
+        setuid(uid).map_err(|e| format!("Failed to set user {uid}: {e}"))?;
+    }
+
+    let e = std::process::Command::new(&setup.program)
+        .args(setup.arguments)
+        .exec();
+    Err(format!(
+        "Failed to run {}: {e}",
+        setup.program.to_string_lossy()
+    ))
+}
+
+/// The arguments of the launcher, as passed by [`wrap`].
+#[derive(Debug, PartialEq)]
+struct Setup {
+    uid: Option<Uid>,
+    gid: Option<Gid>,
+    groups: Option<Vec<Gid>>,
+    program: OsString,
+    arguments: Vec<OsString>,
+}
+
+impl Setup {
+    /// Parses `<options> -- <program> <arguments>`, following the [`LAUNCH_ARG`].
+    fn parse(mut args: impl Iterator<Item = OsString>) -> Result<Self, String> {
+        let mut uid = None;
+        let mut gid = None;
+        let mut groups = None;
+        loop {
+            let arg = args.next().ok_or("Missing program")?;
+            let arg = arg.to_str().ok_or("Invalid argument")?;
+            if arg == "--" {
+                break;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value of {arg}"))?;
+            let value = value.to_str().ok_or("Invalid argument")?;
+            let id = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid id {value}: {e}"))
+            };
+            match arg {
+                "--uid" => uid = Some(Uid::from_raw(id(value)?)),
+                "--gid" => gid = Some(Gid::from_raw(id(value)?)),
+                "--groups" => {
+                    groups = Some(
+                        value
+                            .split(',')
+                            .filter(|group| !group.is_empty())
+                            .map(|group| id(group).map(Gid::from_raw))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    );
+                }
+                _ => return Err(format!("Unknown option {arg}")),
+            }
+        }
+        let program = args.next().ok_or("Missing program")?;
+        Ok(Self {
+            uid,
+            gid,
+            groups,
+            program,
+            arguments: args.collect(),
+        })
+    }
+}
+
+#[cfg(not(target_vendor = "apple"))]
+fn set_groups(groups: &[nix::unistd::Gid]) -> Result<(), String> {
+    nix::unistd::setgroups(groups).map_err(|e| format!("Failed to set groups: {e}"))
+}
+
+/// Apple platforms have no `setgroups` in [`nix`], so switching fails instead of keeping the groups of the server.
+#[cfg(target_vendor = "apple")]
+fn set_groups(_groups: &[nix::unistd::Gid]) -> Result<(), String> {
+    Err(String::from(
+        "Setting groups is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Setup, String> {
+        Setup::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn parses_identity() {
+        let setup = parse(&[
+            "--uid", "1000", "--gid", "100", "--groups", "100,27", "--", "id", "-u",
+        ]);
+        assert_eq!(
+            setup,
+            Ok(Setup {
+                uid: Some(Uid::from_raw(1000)),
+                gid: Some(Gid::from_raw(100)),
+                groups: Some(vec![Gid::from_raw(100), Gid::from_raw(27)]),
+                program: OsString::from("id"),
+                arguments: vec![OsString::from("-u")],
+            })
+        );
+    }
+
+    #[test]
+    fn parses_without_options() {
+        let setup = parse(&["--", "echo", "--uid", "1"]).unwrap();
+        assert_eq!(setup.uid, None);
+        assert_eq!(setup.gid, None);
+        assert_eq!(setup.groups, None);
+        assert_eq!(setup.arguments, ["--uid", "1"]);
+    }
+
+    #[test]
+    fn parses_empty_groups() {
+        let setup = parse(&["--groups", "", "--", "id"]).unwrap();
+        assert_eq!(setup.groups, Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        assert_eq!(parse(&[]), Err(String::from("Missing program")));
+        assert_eq!(parse(&["--"]), Err(String::from("Missing program")));
+        assert_eq!(
+            parse(&["--uid", "1", "id"]),
+            Err(String::from("Missing value of id"))
+        );
+        assert_eq!(
+            parse(&["--uid"]),
+            Err(String::from("Missing value of --uid"))
+        );
+        assert_eq!(
+            parse(&["--user", "root", "--", "id"]),
+            Err(String::from("Unknown option --user"))
+        );
+        assert!(parse(&["--uid", "root", "--", "id"])
+            .unwrap_err()
+            .starts_with("Invalid id root"));
+        assert!(parse(&["--groups", "1,-2", "--", "id"])
+            .unwrap_err()
+            .starts_with("Invalid id -2"));
+    }
+}
@@ -0,0 +1,187 @@
+//! The script library of `GET /api/scripts` and `POST /api/scripts/{name}/run`.
+//!
+//! Each template is a toml file `{name}.toml` in the scripts directory.
+//! The directory is read on every request, such that templates can be changed without a restart.
+//!
+//! # Example
+//! ```toml
+//! description = "Compresses logs older than some days"
+//! interpreter = "bash"
+//! script = '''
+//! find "$LOG_DIR" -name '*.log' -mtime "+$1" -exec gzip {} +
+//! '''
+//!
+//! [[parameters]]
+//! name = "LOG_DIR"
+//!
+//! [[parameters]]
+//! name = "days"
+//! default = "7"
+//! pass_as = "argument"
+//! ```
+
+use rusty_runner_api::api::{
+    ParameterPassing, ScriptInterpreter, ScriptTemplate, TemplateParameter,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The script templates, which are disabled if no scripts directory is configured.
+#[derive(Debug, Clone)]
+pub struct ScriptLibrary {
+    dir: Option<Arc<Path>>,
+}
+
+/// The content of a template file, named by its file name.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    #[serde(default)]
+    description: Option<String>,
+    interpreter: ScriptInterpreter,
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    parameters: Vec<TemplateParameter>,
+    script: String,
+}
+
+/// A loaded template with its script.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub info: ScriptTemplate,
+    pub script: String,
+}
+
+/// The parameter values of a request, as they are passed to the script.
+#[derive(Debug, Default)]
+pub struct Parameters {
+    pub arguments: Vec<String>,
+    pub environment: Vec<(String, String)>,
+}
+
+impl ScriptLibrary {
+    pub fn new(dir: Option<&Path>) -> Self {
+        Self {
+            dir: dir.map(Arc::from),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Lists the templates by name. Invalid templates are logged and skipped.
+    pub async fn list(&self) -> std::io::Result<Vec<ScriptTemplate>> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        let mut templates = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).filter(|_| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            }) else {
+                continue;
+            };
+            match self.load(name).await {
+                Ok(Some(template)) => templates.push(template.info),
+                Ok(None) => {}
+                Err(reason) => log::warn!(path:debug; "skipped script template: {reason}"),
+            }
+        }
+        templates.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// Loads the template `name`, or `None` if there is no such template.
+    pub async fn load(&self, name: &str) -> Result<Option<Template>, String> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+        if !is_template_name(name) {
+            return Ok(None);
+        }
+        let path = dir.join(format!("{name}.toml"));
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read script template `{name}`: {e}")),
+        };
+        let file: TemplateFile = toml::from_str(&content)
+            .map_err(|e| format!("Invalid script template `{name}`: {e}"))?;
+        let mut names = HashSet::new();
+        for parameter in &file.parameters {
+            if !is_parameter_name(&parameter.name) {
+                return Err(format!(
+                    "Script template `{name}`: invalid parameter name `{}`",
+                    parameter.name
+                ));
+            }
+            if !names.insert(&parameter.name) {
+                return Err(format!(
+                    "Script template `{name}`: duplicate parameter `{}`",
+                    parameter.name
+                ));
+            }
+        }
+
+        let mut info = ScriptTemplate::new(name, file.interpreter);
+        info.description = file.description;
+        info.strict = file.strict;
+        info.parameters = file.parameters;
+        Ok(Some(Template {
+            info,
+            script: file.script,
+        }))
+    }
+}
+
+impl Template {
+    /// Assigns the requested `values` to the parameters, falling back to their defaults.
+    ///
+    /// Rejects unknown and missing parameters.
+    pub fn bind(&self, values: &HashMap<String, String>) -> Result<Parameters, String> {
+        let parameters = &self.info.parameters;
+        if let Some(unknown) = values
+            .keys()
+            .find(|name| parameters.iter().all(|parameter| parameter.name != **name))
+        {
+            return Err(format!("Unknown parameter `{unknown}`"));
+        }
+        let mut bound = Parameters::default();
+        for parameter in parameters {
+            let value = values
+                .get(&parameter.name)
+                .or(parameter.default.as_ref())
+                .ok_or_else(|| format!("Missing parameter `{}`", parameter.name))?
+                .clone();
+            match parameter.pass_as {
+                ParameterPassing::Env => bound.environment.push((parameter.name.clone(), value)),
+                ParameterPassing::Argument => bound.arguments.push(value),
+            }
+        }
+        Ok(bound)
+    }
+}
+
+/// Template names are restricted to file names that cannot escape the scripts directory.
+fn is_template_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parameter names must be valid environment variable names in any shell.
+fn is_parameter_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -3,22 +3,49 @@
 //! Listens on `http://localhost:8000`, e.g. `http://localhost:8000/api/info` unless changed by the [`CliArgs`].
 //! The working directory is determined by [`{std::env::temp_dir()}/rusty-runner`][process::working_directory].
 //!
-//! The paths to bash and powershell are configured in [`CliArgs`] and must be set to support the respective interpreters.
+//! The paths to the interpreters, e.g. bash and powershell, are configured in [`CliArgs`] and must be set to support the respective interpreters.
 
 use axum::routing::get;
 use axum::Router;
-use clap::{Parser, ValueHint};
+use clap::{CommandFactory, FromArgMatches, Parser, ValueHint};
 use log::LevelFilter;
-use std::path::PathBuf;
+use rusty_runner_api::api::ScriptInterpreter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use tokio::signal;
 use tower_http::trace::TraceLayer;
 
+mod bundle;
+mod capabilities;
 mod cleanup;
+mod config;
+mod history;
+mod idempotency;
+mod identity;
+mod interpreter;
+mod job;
+#[cfg(unix)]
+mod launch;
+mod library;
+mod openapi;
+mod output;
 mod process;
+mod process_group;
+mod pty;
 mod routes;
+mod sandbox;
+mod spool;
+
+fn main() -> ExitCode {
+    #[cfg(unix)]
+    if let Some(code) = launch::launch_if_requested() {
+        return code;
+    }
+    serve()
+}
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn serve() -> ExitCode {
     env_logger::builder()
         .filter_level(LevelFilter::Info)
         .filter(Some("tower_http"), LevelFilter::Debug)
@@ -26,43 +53,139 @@ async fn main() -> std::io::Result<()> {
         .parse_default_env()
         .init();
 
-    let args = CliArgs::parse();
-
     log::info!(
         version = env!("CARGO_PKG_VERSION"),
         api_version = rusty_runner_api::api::VERSION;
         "initializing server"
     );
-    log::info!(path:debug = args.powershell_path; "configured powershell");
-    log::info!(path:debug = args.bash_path; "configured bash");
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(reason) => {
+            log::error!("{reason}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Validates the configuration and serves until shut down.
+async fn run() -> Result<(), String> {
+    let (args, config_file) = parse_args().await?;
+    let interpreters = interpreter::Interpreters::new(
+        [
+            (ScriptInterpreter::Bash, args.bash_path),
+            (ScriptInterpreter::Powershell, args.powershell_path),
+            (ScriptInterpreter::Sh, args.sh_path),
+            (ScriptInterpreter::Zsh, args.zsh_path),
+            (ScriptInterpreter::Fish, args.fish_path),
+            (ScriptInterpreter::Python, args.python_path),
+            (ScriptInterpreter::Node, args.node_path),
+            (ScriptInterpreter::Perl, args.perl_path),
+            (ScriptInterpreter::Ruby, args.ruby_path),
+        ],
+        &[
+            (ScriptInterpreter::Bash, args.bash_flags),
+            (ScriptInterpreter::Powershell, args.powershell_flags),
+        ],
+        config_file.interpreters,
+    )?;
+    for (name, interpreter) in interpreters.iter() {
+        log::info!(interpreter:debug = interpreter; "configured {name}");
+    }
     log::info!(path:debug = args.cleanup_max_age; "configured age-based cleanup");
     log::info!(path:debug = args.cleanup_max_size; "configured size-based cleanup");
+    log::info!(users:debug = args.allowed_users, groups:debug = args.allowed_groups; "configured allowed users");
+    log::info!(path:debug = args.bwrap_path, default = args.sandbox_default; "configured sandbox");
+    log::info!(bytes:debug = args.max_output_bytes; "configured output limit");
+    log::info!(path:debug = args.state_dir, max_age:debug = args.history_max_age, max_entries:debug = args.history_max_entries; "configured job history");
+    log::info!(path:debug = args.scripts_dir; "configured script library");
+    log::info!(retention:debug = args.idempotency_retention; "configured idempotency keys");
 
-    // Create the server working directory
-    if !process::working_directory().exists() {
-        tokio::fs::create_dir(process::working_directory())
-            .await
-            .expect("Should be able to write to the temporary directory!");
-    }
+    create_directories(args.state_dir.as_deref(), args.scripts_dir.as_deref()).await?;
+
+    // Bind early, such that connections during the capability detection wait instead of being refused
+    let listener = tokio::net::TcpListener::bind((&*args.host, args.port))
+        .await
+        .map_err(|e| format!("Failed to listen on {}:{}: {e}", args.host, args.port))?;
+    log::info!(
+        on:debug = listener.local_addr().ok();
+        "listening to TCP"
+    );
+
+    let user_policy = identity::UserPolicy::new(args.allowed_users, args.allowed_groups)?;
+    let sandbox = sandbox::Sandbox::new(&args.bwrap_path, args.sandbox_default);
+    let history = history::JobHistory::new(
+        args.state_dir.as_deref(),
+        history::Retention {
+            max_age: args.history_max_age,
+            max_entries: args.history_max_entries,
+        },
+    );
+    let library = library::ScriptLibrary::new(args.scripts_dir.as_deref());
 
     // Start cleaning up regularly
-    cleanup::start_cleanup_task(args.cleanup_max_age, args.cleanup_max_size);
+    cleanup::start_cleanup_task(args.cleanup_max_age, args.cleanup_max_size, history.clone());
+    let capabilities = capabilities::Capabilities::detect(
+        &interpreters,
+        &user_policy,
+        &sandbox,
+        &history,
+        &library,
+        args.max_output_bytes,
+    )
+    .await;
+    log::info!(
+        interpreters:debug = capabilities.interpreters(),
+        features:debug = capabilities.features();
+        "detected capabilities"
+    );
 
     // Setup the service
     let router = Router::new()
-        .nest("/api", routes::routes(args.bash_path, args.powershell_path))
+        .nest(
+            "/api",
+            routes::routes(routes::Config {
+                interpreters,
+                user_policy,
+                sandbox,
+                max_output_bytes: args.max_output_bytes,
+                history,
+                library,
+                idempotency: idempotency::Idempotency::new(args.idempotency_retention),
+                active_jobs: job::ActiveJobs::default(),
+                capabilities,
+            }),
+        )
         .route("/health", get(|| async { "OK" }))
         .layer(TraceLayer::new_for_http());
 
-    let listener = tokio::net::TcpListener::bind((args.host, args.port)).await?;
-    log::info!(
-        on:debug = listener.local_addr()?;
-        "listening to TCP"
-    );
-
     axum::serve(listener, router.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
+        .with_graceful_shutdown(shutdown_signal()?)
         .await
+        .map_err(|e| format!("Failed to serve: {e}"))
+}
+
+/// Parses the command line and environment variables, falling back to the options of the config file.
+async fn parse_args() -> Result<(CliArgs, config::ConfigFile), String> {
+    let args = CliArgs::parse();
+    let Some(path) = &args.config else {
+        return Ok((args, config::ConfigFile::default()));
+    };
+    let config_file = config::ConfigFile::load(path).await?;
+    let command = config_file
+        .apply(CliArgs::command())
+        .map_err(|e| format!("Invalid config file {}: {e}", path.display()))?;
+    let matches = match command.try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => match config_file.rejection(&CliArgs::command(), &e) {
+            Some(reason) => {
+                return Err(format!("Invalid config file {}: {reason}", path.display()));
+            }
+            None => e.exit(),
+        },
+    };
+    let args =
+        CliArgs::from_arg_matches(&matches).map_err(|e| format!("Invalid arguments: {e}"))?;
+    Ok((args, config_file))
 }
 
 /// Runs a server complying with the `rusty_runner_api`.
@@ -70,9 +193,21 @@ async fn main() -> std::io::Result<()> {
 /// By default listens on `http://localhost:8000`, e.g. `http://localhost:8000/api/info` unless changed by the command line arguments.
 /// The working directory is determined by `{std::env::temp_dir()}/rusty-runner`.
 ///
-/// The paths to bash and powershell must be set to support the respective interpreters.
+/// The paths to the interpreters, e.g. bash and powershell, must be set to support the respective interpreters.
 #[derive(Parser)]
 struct CliArgs {
+    /// A toml file setting any of these options by name, e.g. `port = 8080` or `allowed_users = ["ci"]`,
+    /// and further settings, e.g. named interpreters:
+    /// `[interpreters.deno]` with `executable = "deno"`, `arguments = ["run", "{script}"]` and `extension = "ts"`.
+    ///
+    /// The command line and environment variables take precedence over the file.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        env = "RUSTY_RUNNER_CONFIG",
+    )]
+    config: Option<PathBuf>,
     /// The host address for the rusty-runner server.
     /// Keep `127.0.0.1` to protect against access from the outside.
     #[arg(
@@ -104,6 +239,17 @@ struct CliArgs {
         env = "RUSTY_RUNNER_BASH",
     )]
     bash_path: Option<PathBuf>,
+    /// The flags passed to bash before the script, separated by spaces, e.g. `-euo pipefail`.
+    ///
+    /// Requests may also ask for `strict` mode, which adds `-euo pipefail` per script.
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_delimiter = ' ',
+        allow_hyphen_values = true,
+        env = "RUSTY_RUNNER_BASH_FLAGS"
+    )]
+    bash_flags: Vec<String>,
     /// The path of the powershell interpreter. If not set, powershell scripts are not supported.
     /// Can be just the name of the binary if it is in the PATH.
     /// On unix this is often `pwsh`, while windows uses `powershell`.
@@ -114,6 +260,76 @@ struct CliArgs {
         env = "RUSTY_RUNNER_POWERSHELL",
     )]
     powershell_path: Option<PathBuf>,
+    /// The flags passed to powershell before `-File`, separated by spaces.
+    ///
+    /// By default, user profiles are not loaded, prompts fail and the execution policy does not block scripts.
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        value_delimiter = ' ',
+        allow_hyphen_values = true,
+        default_value = "-NoProfile -NonInteractive -ExecutionPolicy Bypass",
+        env = "RUSTY_RUNNER_POWERSHELL_FLAGS"
+    )]
+    powershell_flags: Vec<String>,
+    /// The path of the sh interpreter. If not set, sh scripts are not supported.
+    /// Usually `sh`, which is commonly `dash` or `bash` in POSIX mode.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_SH",
+    )]
+    sh_path: Option<PathBuf>,
+    /// The path of the zsh interpreter. If not set, zsh scripts are not supported.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_ZSH",
+    )]
+    zsh_path: Option<PathBuf>,
+    /// The path of the fish interpreter. If not set, fish scripts are not supported.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_FISH",
+    )]
+    fish_path: Option<PathBuf>,
+    /// The path of the python interpreter. If not set, python scripts are not supported.
+    /// On unix this is often `python3`, while windows uses `python` or `py`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_PYTHON",
+    )]
+    python_path: Option<PathBuf>,
+    /// The path of the node interpreter. If not set, node scripts are not supported.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_NODE",
+    )]
+    node_path: Option<PathBuf>,
+    /// The path of the perl interpreter. If not set, perl scripts are not supported.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_PERL",
+    )]
+    perl_path: Option<PathBuf>,
+    /// The path of the ruby interpreter. If not set, ruby scripts are not supported.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        env = "RUSTY_RUNNER_RUBY",
+    )]
+    ruby_path: Option<PathBuf>,
     /// The maximum age for entries in the working directory, e.g. `1.5d` for 1.5 days.
     /// Also supported suffixes: `w` for weeks, `h` for hours.
     #[arg(
@@ -125,7 +341,8 @@ struct CliArgs {
     )]
     cleanup_max_age: Option<std::time::Duration>,
     /// The maximum size for entries in the working directory, e.g. `2.5G` or `2.5GB` for 2.5 gigabytes.
-    /// Also supported suffixes: `M`/`MB` for megabytes, `T`/`TB` for terrabytes.
+    /// Also supported suffixes: `K`/`KB` for kilobytes, `M`/`MB` for megabytes, `T`/`TB` for terrabytes,
+    /// and the binary forms `KiB`, `MiB`, `GiB` and `TiB`.
     ///
     /// Note that we use binary definitions of giga, i.e. 1GB = files sizes that amount to 1024^3 bytes
     #[arg(
@@ -136,30 +353,177 @@ struct CliArgs {
         value_parser = parse_size
     )]
     cleanup_max_size: Option<usize>,
+    /// The unix users that commands may be run as, e.g. `nobody,builder`. If not set, switching users is not supported.
+    ///
+    /// Switching requires the server to have the privileges to do so, commonly by running as root.
+    /// Users with supplementary groups besides their primary group are switched to by running
+    /// the server binary itself in an internal launcher mode, which sets the groups before the command starts.
+    #[arg(
+        long,
+        value_name = "USERS",
+        value_hint = ValueHint::Username,
+        value_delimiter = ',',
+        env = "RUSTY_RUNNER_ALLOWED_USERS",
+    )]
+    allowed_users: Vec<String>,
+    /// The unix groups that commands may be run as, e.g. `nogroup,builders`.
+    /// The primary group of an allowed user is always allowed for that user.
+    #[arg(
+        long,
+        value_name = "GROUPS",
+        value_hint = ValueHint::Other,
+        value_delimiter = ',',
+        env = "RUSTY_RUNNER_ALLOWED_GROUPS",
+    )]
+    allowed_groups: Vec<String>,
+    /// The path of the bubblewrap binary used to sandbox commands on linux.
+    /// Can be just the name of the binary if it is in the PATH.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::ExecutablePath,
+        default_value = "bwrap",
+        env = "RUSTY_RUNNER_BWRAP",
+    )]
+    bwrap_path: PathBuf,
+    /// Sandbox commands by default, unless the request opts out. See `--bwrap-path`.
+    #[arg(long, env = "RUSTY_RUNNER_SANDBOX_DEFAULT")]
+    sandbox_default: bool,
+    /// The maximum size of the captured output per stream and command, e.g. `16M` for 16 megabytes.
+    /// Requests may only lower this limit. Supports the same suffixes as `--cleanup-max-size`.
+    #[arg(
+        long,
+        value_name = "MB",
+        value_hint = ValueHint::Other,
+        env = "RUSTY_RUNNER_MAX_OUTPUT",
+        value_parser = parse_size
+    )]
+    max_output_bytes: Option<usize>,
+    /// The directory where the job history is persisted. If not set, jobs are not recorded.
+    ///
+    /// Recorded jobs can be queried by `GET /api/jobs` and `GET /api/job/{id}`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::DirPath,
+        env = "RUSTY_RUNNER_STATE_DIR",
+    )]
+    state_dir: Option<PathBuf>,
+    /// The maximum age of jobs in the job history, e.g. `30d`. Older jobs are removed by the periodic cleanup.
+    /// Supports the same suffixes as `--cleanup-max-age`.
+    #[arg(
+        long,
+        value_name = "DAYS",
+        value_hint = ValueHint::Other,
+        env = "RUSTY_RUNNER_HISTORY_MAX_AGE",
+        value_parser = parse_duration
+    )]
+    history_max_age: Option<std::time::Duration>,
+    /// The maximum number of jobs in the job history.
+    /// The oldest jobs exceeding it are removed by the periodic cleanup.
+    #[arg(
+        long,
+        value_name = "COUNT",
+        value_hint = ValueHint::Other,
+        env = "RUSTY_RUNNER_HISTORY_MAX_ENTRIES"
+    )]
+    history_max_entries: Option<usize>,
+    /// The directory of the script templates, each a toml file `{name}.toml`.
+    /// If not set, the script library is disabled.
+    ///
+    /// Templates are listed by `GET /api/scripts` and run by `POST /api/scripts/{name}/run`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::DirPath,
+        env = "RUSTY_RUNNER_SCRIPTS_DIR",
+    )]
+    scripts_dir: Option<PathBuf>,
+    /// How long the responses to requests with an `Idempotency-Key` header are kept, e.g. `24h`.
+    /// Retried requests with the same key within this window receive the original response instead of running again.
+    /// Supports the same suffixes as `--cleanup-max-age`.
+    #[arg(
+        long,
+        value_name = "HOURS",
+        value_hint = ValueHint::Other,
+        default_value = "24h",
+        env = "RUSTY_RUNNER_IDEMPOTENCY_RETENTION",
+        value_parser = parse_duration
+    )]
+    idempotency_retention: std::time::Duration,
 }
 
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
+/// Creates the server working directory and the state directory for the job history,
+/// and checks that the scripts directory exists.
+async fn create_directories(
+    state_dir: Option<&Path>,
+    scripts_dir: Option<&Path>,
+) -> Result<(), String> {
+    // Create the server working directory
+    let working_directory = process::working_directory();
+    if !working_directory.exists() {
+        tokio::fs::create_dir(&working_directory)
             .await
-            .expect("failed to install SIGINT (ctrl+c) handler");
-    };
+            .map_err(|e| {
+                format!(
+                    "Failed to create the working directory {}: {e}",
+                    working_directory.display()
+                )
+            })?;
+    }
 
+    // Create the state directory for the job history
+    if let Some(state_dir) = state_dir {
+        tokio::fs::create_dir_all(state_dir).await.map_err(|e| {
+            format!(
+                "Failed to create the state directory {}: {e}",
+                state_dir.display()
+            )
+        })?;
+    }
+
+    if let Some(scripts_dir) = scripts_dir {
+        if !tokio::fs::metadata(scripts_dir)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            return Err(format!(
+                "The scripts directory {} does not exist",
+                scripts_dir.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Installs the handlers for SIGINT (ctrl+c) and SIGTERM, returning a future that resolves on either.
+fn shutdown_signal() -> Result<impl std::future::Future<Output = ()>, String> {
     #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("failed to install SIGTERM handler")
-            .recv()
-            .await;
+    let (mut ctrl_c, mut terminate) = {
+        use signal::unix::{signal, SignalKind};
+        (
+            signal(SignalKind::interrupt())
+                .map_err(|e| format!("Failed to install SIGINT (ctrl+c) handler: {e}"))?,
+            signal(SignalKind::terminate())
+                .map_err(|e| format!("Failed to install SIGTERM handler: {e}"))?,
+        )
     };
+    #[cfg(windows)]
+    let mut ctrl_c =
+        signal::windows::ctrl_c().map_err(|e| format!("Failed to install ctrl+c handler: {e}"))?;
 
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        () = ctrl_c => log::info!("received SIGINT (ctrl+c), shutting down"),
-        () = terminate => log::info!("received SIGTERM, shutting down"),
-    }
+    Ok(async move {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = ctrl_c.recv() => log::info!("received SIGINT (ctrl+c), shutting down"),
+            _ = terminate.recv() => log::info!("received SIGTERM, shutting down"),
+        }
+        #[cfg(windows)]
+        {
+            ctrl_c.recv().await;
+            log::info!("received ctrl+c, shutting down");
+        }
+    })
 }
 
 fn parse_suffixed_num(s: &str) -> Result<(f32, String), String> {
@@ -197,9 +561,10 @@ fn parse_size(s: &str) -> Result<usize, String> {
         return Err("Size cannot be negative".to_string());
     }
     match unit.trim().to_ascii_uppercase().as_str() {
-        "M" | "MB" => Ok((num * 1024.0 * 1024.0).round() as usize),
-        "G" | "GB" => Ok((num * 1024.0 * 1024.0 * 1024.0).round() as usize),
-        "T" | "TB" => Ok((num * 1024.0 * 1024.0 * 1024.0 * 1024.0).round() as usize),
+        "K" | "KB" | "KIB" => Ok((num * 1024.0).round() as usize),
+        "M" | "MB" | "MIB" => Ok((num * 1024.0 * 1024.0).round() as usize),
+        "G" | "GB" | "GIB" => Ok((num * 1024.0 * 1024.0 * 1024.0).round() as usize),
+        "T" | "TB" | "TIB" => Ok((num * 1024.0 * 1024.0 * 1024.0 * 1024.0).round() as usize),
         _ => Err(format!("Invalid unit for size: {unit}")),
     }
 }
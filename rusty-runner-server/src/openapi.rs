@@ -0,0 +1,181 @@
+//! The `OpenAPI` document of the routes, generated from the handlers and the `rusty_runner_api` types.
+
+use crate::routes;
+use axum::Json;
+use rusty_runner_api::api::VERSION;
+use utoipa::openapi::path::{OperationBuilder, ParameterBuilder, ParameterIn};
+use utoipa::openapi::{ContentBuilder, HttpMethod, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "rusty-runner",
+        description = "A remote script execution server. See the `rusty-runner-api` crate for details.",
+    ),
+    paths(
+        routes::info,
+        routes::run_command,
+        routes::run_script,
+        routes::run_script_json,
+        routes::run_script_bundle,
+        routes::scripts,
+        routes::run_template,
+        routes::jobs,
+        routes::job,
+        openapi,
+    ),
+    modifiers(&FileRoute, &ApiVersion, &HiddenDocLines),
+)]
+struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    responses((status = 200, description = "This OpenAPI document")),
+)]
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    log::debug!("sending openapi");
+    Json(ApiDoc::openapi())
+}
+
+/// Adds `GET /api/file/{path}`, which is served by a `ServeDir` instead of a handler.
+struct FileRoute;
+
+impl Modify for FileRoute {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let operation = OperationBuilder::new()
+            .description(Some(
+                "Fetches a file from the working directory of the runner.",
+            ))
+            .parameter(
+                ParameterBuilder::new()
+                    .name("path")
+                    .parameter_in(ParameterIn::Path)
+                    .description(Some("The path relative to the working directory")),
+            )
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description("The file content")
+                    .content("application/octet-stream", ContentBuilder::new().build()),
+            )
+            .response("404", ResponseBuilder::new().description("File not found"))
+            .build();
+        openapi
+            .paths
+            .add_path_operation(file_path(), vec![HttpMethod::Get], operation);
+    }
+}
+
+/// The documented path of the files served by [`routes::FILE_ROUTE`].
+fn file_path() -> String {
+    format!("/api{}/{{path}}", routes::FILE_ROUTE)
+}
+
+/// Versions the document by the api instead of the server.
+struct ApiVersion;
+
+impl Modify for ApiVersion {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.version = String::from(VERSION);
+    }
+}
+
+/// Removes the lines hidden from rustdoc, i.e. lines starting with `#` in code blocks,
+/// from the schema descriptions, as they are only relevant to the doc tests.
+struct HiddenDocLines;
+
+impl Modify for HiddenDocLines {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        use utoipa::openapi::schema::Schema;
+        use utoipa::openapi::RefOr;
+
+        let Some(components) = &mut openapi.components else {
+            return;
+        };
+        for schema in components.schemas.values_mut() {
+            let description = match schema {
+                RefOr::T(Schema::Object(object)) => &mut object.description,
+                RefOr::T(Schema::AllOf(all_of)) => &mut all_of.description,
+                RefOr::T(Schema::OneOf(one_of)) => &mut one_of.description,
+                _ => continue,
+            };
+            if let Some(description) = description {
+                *description = strip_hidden_lines(description);
+            }
+        }
+    }
+}
+
+fn strip_hidden_lines(description: &str) -> String {
+    let mut in_code = false;
+    description
+        .lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                return true;
+            }
+            let hidden = line.trim_start() == "#" || line.trim_start().starts_with("# ");
+            !(in_code && hidden)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// The documented and the routed endpoints must match exactly, such that neither drifts.
+    #[test]
+    fn documents_all_routes() {
+        let documented: BTreeSet<(String, String)> = ApiDoc::openapi()
+            .paths
+            .paths
+            .into_iter()
+            .flat_map(|(path, item)| {
+                [
+                    ("GET", item.get.is_some()),
+                    ("PUT", item.put.is_some()),
+                    ("POST", item.post.is_some()),
+                    ("DELETE", item.delete.is_some()),
+                    ("PATCH", item.patch.is_some()),
+                ]
+                .into_iter()
+                .filter(|(_, documented)| *documented)
+                .map(move |(method, _)| (path.clone(), method.to_string()))
+            })
+            .collect();
+
+        let mut routed: BTreeSet<(String, String)> = routes::api_routes()
+            .into_iter()
+            .map(|(path, method, _)| {
+                // Axum captures path segments by `:name`, while OpenAPI uses `{name}`.
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(name) => format!("{{{name}}}"),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (format!("/api{path}"), method.to_string())
+            })
+            .collect();
+        routed.insert((file_path(), String::from("GET")));
+
+        let undocumented: Vec<_> = routed.difference(&documented).collect();
+        let unrouted: Vec<_> = documented.difference(&routed).collect();
+        assert!(
+            undocumented.is_empty(),
+            "Undocumented routes: {undocumented:?}"
+        );
+        assert!(
+            unrouted.is_empty(),
+            "Documented but not routed: {unrouted:?}"
+        );
+    }
+}
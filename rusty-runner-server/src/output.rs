@@ -0,0 +1,142 @@
+//! Capturing command output with an optional size limit.
+//!
+//! Output beyond the limit is still read, such that the command doesn't block on a full pipe,
+//! but it is discarded except for the configured head and/or tail.
+//! Optionally the chunks are recorded with timestamps, such that streams can be interleaved.
+
+use rusty_runner_api::api::OutputTruncation;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The captured output of a single stream.
+pub struct Captured {
+    /// The kept bytes.
+    pub bytes: Vec<u8>,
+    /// The total number of bytes written by the command.
+    pub total: u64,
+    /// The timestamped chunks, if recorded.
+    pub chunks: Vec<TimedChunk>,
+}
+
+/// A chunk of output as read from the stream.
+pub struct TimedChunk {
+    /// The time since the start of the command.
+    pub elapsed: Duration,
+    pub bytes: Vec<u8>,
+}
+
+impl Captured {
+    /// Returns the total number of bytes if the output was truncated.
+    pub fn truncated_total(&self) -> Option<u64> {
+        (self.total > self.bytes.len() as u64).then_some(self.total)
+    }
+}
+
+/// Keeps the head and/or tail of a stream up to a limit.
+pub struct CappedBuffer {
+    head: Vec<u8>,
+    head_limit: usize,
+    tail: VecDeque<u8>,
+    tail_limit: usize,
+    total: u64,
+    recorder: Option<Recorder>,
+}
+
+/// Records timestamped chunks from the start of a stream up to a limit.
+struct Recorder {
+    start: Instant,
+    remaining: usize,
+    chunks: Vec<TimedChunk>,
+}
+
+impl CappedBuffer {
+    /// Creates a buffer that keeps at most `limit` bytes, or everything if `None`.
+    pub fn new(limit: Option<usize>, truncation: OutputTruncation) -> Self {
+        let Some(limit) = limit else {
+            return Self::new(Some(usize::MAX), OutputTruncation::Head);
+        };
+        let head_limit = match truncation {
+            OutputTruncation::Head => limit,
+            OutputTruncation::Tail => 0,
+            OutputTruncation::HeadAndTail => limit / 2,
+        };
+        Self {
+            head: Vec::new(),
+            head_limit,
+            tail: VecDeque::new(),
+            tail_limit: limit - head_limit,
+            total: 0,
+            recorder: None,
+        }
+    }
+
+    /// Additionally records the first `limit` bytes as chunks, timestamped relative to `start`.
+    pub fn record_chunks(mut self, start: Instant, limit: Option<usize>) -> Self {
+        self.recorder = Some(Recorder {
+            start,
+            remaining: limit.unwrap_or(usize::MAX),
+            chunks: Vec::new(),
+        });
+        self
+    }
+
+    pub fn extend(&mut self, mut chunk: &[u8]) {
+        self.total += chunk.len() as u64;
+
+        if let Some(recorder) = &mut self.recorder {
+            let recorded = &chunk[..recorder.remaining.min(chunk.len())];
+            if !recorded.is_empty() {
+                recorder.remaining -= recorded.len();
+                recorder.chunks.push(TimedChunk {
+                    elapsed: recorder.start.elapsed(),
+                    bytes: recorded.to_vec(),
+                });
+            }
+        }
+
+        let head_space = self.head_limit - self.head.len();
+        if head_space > 0 {
+            let (head, rest) = chunk.split_at(head_space.min(chunk.len()));
+            self.head.extend_from_slice(head);
+            chunk = rest;
+        }
+
+        // Only the last `tail_limit` bytes of the chunk can possibly be kept.
+        let chunk = &chunk[chunk.len().saturating_sub(self.tail_limit)..];
+        self.tail.extend(chunk);
+        let excess = self.tail.len().saturating_sub(self.tail_limit);
+        self.tail.drain(..excess);
+    }
+
+    pub fn finish(self) -> Captured {
+        let mut bytes = self.head;
+        bytes.extend(self.tail);
+        Captured {
+            bytes,
+            total: self.total,
+            chunks: self
+                .recorder
+                .map(|recorder| recorder.chunks)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Reads the stream until its end, keeping the output as configured by the `buffer`.
+pub async fn read_capped(
+    pipe: Option<impl AsyncRead + Unpin>,
+    mut buffer: CappedBuffer,
+) -> std::io::Result<Captured> {
+    if let Some(mut pipe) = pipe {
+        let mut chunk = vec![0; 8192];
+        loop {
+            let n = pipe.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            buffer.extend(&chunk[..n]);
+        }
+    }
+    Ok(buffer.finish())
+}
@@ -1,6 +1,20 @@
-use std::{path::PathBuf, time::Instant};
+use std::{
+    path::{Component, Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::Instant,
+};
 
-use rusty_runner_api::api::{RunResponse, RunStatus};
+use crate::identity::Identity;
+use crate::job::Job;
+use crate::output::{read_capped, CappedBuffer, Captured, TimedChunk};
+use crate::process_group::ProcessGroup;
+use crate::pty::{Terminal, TerminalSize};
+use crate::sandbox::{self, SetupCheck};
+use crate::spool::Spool;
+use rusty_runner_api::api::{
+    OrphanProcess, OutputBytes, OutputEncoding, OutputStream, OutputTruncation, RunResponse,
+    RunStatus, TerminationSignal, TranscriptChunk,
+};
 use tokio::process::Command;
 
 /// The directory where all commands will be executed in.
@@ -10,43 +24,242 @@ pub fn working_directory() -> PathBuf {
     path
 }
 
-pub async fn process(
-    id: u64,
-    mut command: Command,
-    return_stdout: bool,
-    return_stderr: bool,
-) -> RunResponse {
+/// Joins the relative `path` to `base`, unless it is empty or could escape `base`, e.g. by `..`.
+pub fn contained_path(base: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    let is_plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    (!path.is_empty() && is_plain).then(|| base.join(relative))
+}
+
+/// The options for running a command with [`process`].
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)] // independent options of the api
+pub struct ProcessOptions {
+    /// Return the captured `stdout` in the response.
+    pub return_stdout: bool,
+    /// Return the captured `stderr` in the response.
+    pub return_stderr: bool,
+    /// Kill the remaining process group once the command exits.
+    pub kill_orphans: bool,
+    /// Wrap the command in the [`Sandbox`](crate::sandbox::Sandbox).
+    pub sandboxed: bool,
+    /// Set by the [`Sandbox`](crate::sandbox::Sandbox) wrapping the command,
+    /// such that sandbox setup errors are reported as failures.
+    pub sandbox_setup: Option<SetupCheck>,
+    /// Attach a pseudo terminal of the given size, whose merged output is returned as `stdout`.
+    pub tty: Option<TerminalSize>,
+    /// The maximum number of bytes captured per stream.
+    pub max_output_bytes: Option<usize>,
+    /// Which part of a stream exceeding `max_output_bytes` is kept.
+    pub truncation: OutputTruncation,
+    /// The json encoding of the returned streams.
+    pub output_encoding: OutputEncoding,
+    /// The files that streams are written to instead of being captured.
+    pub spool: Spool,
+    /// Return a transcript interleaving the streams.
+    pub transcript: bool,
+    /// The user and groups to run as, instead of the ones of the server.
+    pub identity: Option<Identity>,
+}
+
+pub async fn process(job: &Job, mut command: Command, options: ProcessOptions) -> RunResponse {
+    if let Some(identity) = &options.identity {
+        command = identity.apply(command);
+    }
+
+    // Run each command in its own process group, such that its descendants can be killed.
+    #[cfg(unix)]
+    command.process_group(0);
+
     // Just run the command and wait for the completion.
     let start = Instant::now();
-    let result = command.output().await;
+    let result = run(command, &options, start).await;
     let end = Instant::now();
     let time_taken = end - start;
 
     match result {
-        Ok(out) => {
+        Ok(mut out) => {
+            if options
+                .sandbox_setup
+                .as_ref()
+                .is_some_and(|setup| !setup.succeeded())
+            {
+                let reason = sandbox::setup_error(&out.stderr.bytes);
+                log::info!(job; "Sandbox failed: {reason}");
+                return job.failure(reason);
+            }
             // FIXME: zero/one line stdout
-            log::debug!(id; "Status: {}", out.status);
-            log::debug!(id; "Stdout: {}", String::from_utf8_lossy(&out.stdout).trim());
-            log::debug!(id; "Stderr: {}", String::from_utf8_lossy(&out.stderr).trim());
+            log::debug!(job; "Status: {}", out.status);
+            log::debug!(job; "Stdout: {}", String::from_utf8_lossy(&out.stdout.bytes).trim());
+            log::debug!(job; "Stderr: {}", String::from_utf8_lossy(&out.stderr.bytes).trim());
+            if !out.killed_orphans.is_empty() {
+                log::info!(job; "Killed orphans: {:?}", out.killed_orphans);
+            }
             // TODO: write logs to file ?
-            RunResponse {
-                id,
-                status: RunStatus::Completed {
-                    exit_code: out.status.code().unwrap_or(-1001),
-                    time_taken,
-                    stderr: Some(out.stderr).filter(|_| return_stderr),
-                    stdout: Some(out.stdout).filter(|_| return_stdout),
-                },
+            let (signal, core_dumped) = termination_signal(out.status);
+            let transcript = options.transcript.then(|| {
+                transcript(
+                    std::mem::take(&mut out.stdout.chunks),
+                    std::mem::take(&mut out.stderr.chunks),
+                    options.output_encoding,
+                )
+            });
+            let mut status = RunStatus::completed(out.status.code().unwrap_or(-1001), time_taken);
+            if let RunStatus::Completed {
+                signal: status_signal,
+                core_dumped: status_core_dumped,
+                killed_orphans,
+                transcript: status_transcript,
+                stdout,
+                stderr,
+                stdout_total_bytes,
+                stderr_total_bytes,
+                ..
+            } = &mut status
+            {
+                *status_signal = signal;
+                *status_core_dumped = core_dumped;
+                *killed_orphans = out.killed_orphans;
+                *status_transcript = transcript;
+                *stdout_total_bytes = out.stdout.truncated_total();
+                *stderr_total_bytes = out.stderr.truncated_total();
+                *stderr = Some(out.stderr.bytes)
+                    .filter(|_| options.return_stderr)
+                    .map(|bytes| OutputBytes::new(bytes, options.output_encoding));
+                *stdout = Some(out.stdout.bytes)
+                    .filter(|_| options.return_stdout)
+                    .map(|bytes| OutputBytes::new(bytes, options.output_encoding));
             }
+            job.response(status)
         }
         Err(e) => {
-            log::info!(id; "Failed: {e:?}");
-            RunResponse {
-                id,
-                status: RunStatus::Failure {
-                    reason: e.to_string(),
-                },
-            }
+            log::info!(job; "Failed: {e:?}");
+            job.failure(e.to_string())
         }
     }
 }
+
+/// The outcome of a command that ran to completion.
+struct Completion {
+    status: ExitStatus,
+    stdout: Captured,
+    stderr: Captured,
+    killed_orphans: Vec<OrphanProcess>,
+}
+
+/// Runs the command to completion, analogous to [`Command::output`].
+///
+/// If `kill_orphans` is set, the remaining process group is killed as soon as the command exits,
+/// instead of waiting for background children to close `stdout` and `stderr`.
+///
+/// If `tty` is set, the terminal output is returned as `stdout` and `stderr` is empty.
+/// Spooled streams are written to their files and returned empty.
+async fn run(
+    mut command: Command,
+    options: &ProcessOptions,
+    start: Instant,
+) -> std::io::Result<Completion> {
+    command.stdin(Stdio::null());
+    let terminal = if let Some(size) = options.tty {
+        Some(Terminal::attach(&mut command, size)?)
+    } else {
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        None
+    };
+    options
+        .spool
+        .attach(&mut command, options.identity.as_ref())
+        .await?;
+    let mut child = match &options.sandbox_setup {
+        Some(setup) => setup.spawn(&mut command)?,
+        None => command.spawn()?,
+    };
+    // Closes our copies of the terminal, such that reading it ends with the command.
+    drop(command);
+    let group = child
+        .id()
+        .filter(|_| options.kill_orphans)
+        .map(ProcessGroup::new);
+
+    let buffer = || {
+        let buffer = CappedBuffer::new(options.max_output_bytes, options.truncation);
+        if options.transcript {
+            buffer.record_chunks(start, options.max_output_bytes)
+        } else {
+            buffer
+        }
+    };
+    let stdout_pipe = child.stdout.take();
+    let stdout = async {
+        match terminal {
+            Some(terminal) => terminal.read_capped(buffer()).await,
+            None => read_capped(stdout_pipe, buffer()).await,
+        }
+    };
+    let stderr = read_capped(child.stderr.take(), buffer());
+    let wait = async {
+        match group {
+            Some(group) => group.wait(&mut child).await,
+            None => child.wait().await.map(|status| (status, Vec::new())),
+        }
+    };
+
+    let (result, stdout, stderr) = tokio::join!(wait, stdout, stderr);
+    let (status, killed_orphans) = result?;
+    Ok(Completion {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+        killed_orphans,
+    })
+}
+
+/// Interleaves the recorded chunks of both streams by their timestamps.
+fn transcript(
+    stdout: Vec<TimedChunk>,
+    stderr: Vec<TimedChunk>,
+    encoding: OutputEncoding,
+) -> Vec<TranscriptChunk> {
+    let stdout = stdout
+        .into_iter()
+        .map(|chunk| (OutputStream::Stdout, chunk));
+    let stderr = stderr
+        .into_iter()
+        .map(|chunk| (OutputStream::Stderr, chunk));
+    let mut chunks: Vec<_> = stdout.chain(stderr).collect();
+    chunks.sort_by_key(|(_, chunk)| chunk.elapsed);
+    chunks
+        .into_iter()
+        .map(|(stream, chunk)| {
+            TranscriptChunk::new(
+                stream,
+                chunk.elapsed,
+                OutputBytes::new(chunk.bytes, encoding),
+            )
+        })
+        .collect()
+}
+
+/// Returns the signal that terminated the process and whether it dumped core.
+#[cfg(unix)]
+fn termination_signal(status: ExitStatus) -> (Option<TerminationSignal>, bool) {
+    use std::os::unix::process::ExitStatusExt;
+
+    let signal = status.signal().map(|number| {
+        TerminationSignal::new(
+            number,
+            nix::sys::signal::Signal::try_from(number)
+                .map_or("UNKNOWN", nix::sys::signal::Signal::as_str),
+        )
+    });
+    (signal, status.core_dumped())
+}
+
+/// Signals don't exist on windows.
+#[cfg(windows)]
+fn termination_signal(_status: ExitStatus) -> (Option<TerminationSignal>, bool) {
+    (None, false)
+}
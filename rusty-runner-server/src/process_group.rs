@@ -0,0 +1,139 @@
+//! Tracks the process group of a command, so that background children can be killed.
+//!
+//! On unix every command is spawned as the leader of a new process group,
+//! such that all its descendants can be signalled at once.
+//! Windows has no process groups, so this is a no-op there.
+
+use rusty_runner_api::api::OrphanProcess;
+use std::process::ExitStatus;
+use tokio::process::Child;
+
+/// Kills the remaining members of a process group, either explicitly or when dropped.
+///
+/// Dropping happens if the request is cancelled, e.g. by the client disconnecting.
+pub struct ProcessGroup {
+    #[cfg_attr(windows, allow(dead_code))]
+    pgid: u32,
+    armed: bool,
+}
+
+impl ProcessGroup {
+    /// Tracks the process group led by the process `pgid`.
+    pub fn new(pgid: u32) -> Self {
+        Self { pgid, armed: true }
+    }
+
+    /// Waits for the leader `child` to exit, then kills the remaining group
+    /// and returns the exit status of the leader along with the processes killed.
+    ///
+    /// Where `waitid` can wait without reaping, the group is killed before the leader is reaped,
+    /// such that its pid cannot be reused and the kill cannot hit an unrelated process group.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub async fn wait(
+        self,
+        child: &mut Child,
+    ) -> std::io::Result<(ExitStatus, Vec<OrphanProcess>)> {
+        self.exited().await;
+        let orphans = self.kill_remaining();
+        let status = child.wait().await?;
+        Ok((status, orphans))
+    }
+
+    /// Without `waitid` the leader has to be reaped first, as killing the group would kill it too.
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+    pub async fn wait(
+        self,
+        child: &mut Child,
+    ) -> std::io::Result<(ExitStatus, Vec<OrphanProcess>)> {
+        let status = child.wait().await?;
+        Ok((status, self.kill_remaining()))
+    }
+
+    /// Waits until the leader exits, but leaves it to be reaped by [`Child::wait`].
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    async fn exited(&self) {
+        use nix::sys::wait::{waitid, Id, WaitPidFlag};
+        use nix::unistd::Pid;
+
+        let Ok(pid) = i32::try_from(self.pgid) else {
+            return;
+        };
+        let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOWAIT;
+        let result = tokio::task::spawn_blocking(move || loop {
+            match waitid(Id::Pid(Pid::from_raw(pid)), flags) {
+                Err(nix::errno::Errno::EINTR) => {}
+                result => return result,
+            }
+        })
+        .await;
+        if let Ok(Err(e)) = result {
+            log::warn!(pgid = self.pgid, e:debug; "failed to wait for process group leader");
+        }
+    }
+
+    /// Kills all processes remaining in the group and returns the ones found,
+    /// which excludes the exited leader.
+    ///
+    /// Listing the processes is only supported on linux, as it requires `/proc`,
+    /// but the group is killed on any unix.
+    fn kill_remaining(mut self) -> Vec<OrphanProcess> {
+        let mut orphans = list_members(self.pgid);
+        orphans.retain(|orphan| orphan.pid != self.pgid);
+        self.kill();
+        self.armed = false;
+        orphans
+    }
+
+    #[cfg(unix)]
+    fn kill(&self) {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        let Ok(pgid) = i32::try_from(self.pgid) else {
+            return;
+        };
+        match killpg(Pid::from_raw(pgid), Signal::SIGKILL) {
+            // No process remaining in the group.
+            Ok(()) | Err(nix::errno::Errno::ESRCH) => {}
+            Err(e) => log::warn!(pgid, e:debug; "failed to kill process group"),
+        }
+    }
+
+    #[cfg(windows)]
+    #[allow(clippy::unused_self)]
+    fn kill(&self) {}
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if self.armed {
+            log::debug!(pgid = self.pgid; "killing process group of cancelled command");
+            self.kill();
+        }
+    }
+}
+
+/// Lists all processes in the process group by scanning `/proc/{pid}/stat`.
+#[cfg(unix)]
+fn list_members(pgid: u32) -> Vec<OrphanProcess> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            // Format is `pid (name) state ppid pgrp ...`, where the name may contain spaces and parentheses.
+            let (head, tail) = stat.rsplit_once(')')?;
+            let (_, name) = head.split_once('(')?;
+            let pgrp: u32 = tail.split_whitespace().nth(2)?.parse().ok()?;
+            (pgrp == pgid).then(|| OrphanProcess::new(pid, name))
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn list_members(_pgid: u32) -> Vec<OrphanProcess> {
+    Vec::new()
+}
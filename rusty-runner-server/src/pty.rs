@@ -0,0 +1,71 @@
+//! Running commands attached to a pseudo terminal.
+//!
+//! Only `stdout` and `stderr` are attached, `stdin` stays closed such that commands cannot wait for input.
+//! The terminal is not the controlling terminal of the command, as that would require an `unsafe` pre-exec hook,
+//! but `isatty` and the terminal size work as expected.
+
+use crate::output::{CappedBuffer, Captured};
+use std::io;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+/// The size of a pseudo terminal in characters.
+#[derive(Debug, Clone, Copy)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub columns: u16,
+}
+
+/// The reading end of a pseudo terminal attached to a command.
+#[cfg_attr(windows, allow(dead_code))]
+pub struct Terminal {
+    master: File,
+}
+
+impl Terminal {
+    /// Opens a new pseudo terminal and attaches it as `stdout` and `stderr` of the command.
+    ///
+    /// The command must be dropped after spawning, otherwise [`read_capped`](Self::read_capped) never returns.
+    #[cfg(unix)]
+    pub fn attach(command: &mut Command, size: TerminalSize) -> io::Result<Self> {
+        let winsize = nix::pty::Winsize {
+            ws_row: size.rows,
+            ws_col: size.columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let pty = nix::pty::openpty(&winsize, None)?;
+        command.stdout(pty.slave.try_clone()?);
+        command.stderr(pty.slave);
+        command.env("TERM", "xterm-256color");
+        Ok(Self {
+            master: File::from_std(std::fs::File::from(pty.master)),
+        })
+    }
+
+    /// Pseudo terminals are not supported on windows.
+    #[cfg(windows)]
+    pub fn attach(_command: &mut Command, _size: TerminalSize) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TTY not supported on windows",
+        ))
+    }
+
+    /// Reads the terminal output until the command and all its children have closed the terminal.
+    pub async fn read_capped(mut self, mut buffer: CappedBuffer) -> io::Result<Captured> {
+        let mut chunk = vec![0; 8192];
+        loop {
+            match self.master.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(n) => buffer.extend(&chunk[..n]),
+                // Linux reports a terminal without any remaining writers as `EIO`.
+                #[cfg(unix)]
+                Err(e) if e.raw_os_error() == Some(nix::errno::Errno::EIO as i32) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(buffer.finish())
+    }
+}
@@ -1,16 +1,27 @@
-use crate::process::{process, working_directory};
-use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use crate::bundle::Bundle;
+use crate::capabilities::{self, Capabilities};
+use crate::history::JobHistory;
+use crate::idempotency::{self, Idempotency, Outcome};
+use crate::identity::UserPolicy;
+use crate::interpreter::Interpreters;
+use crate::job::{ActiveJobs, Job};
+use crate::library::ScriptLibrary;
+use crate::openapi;
+use crate::process::{process, working_directory, ProcessOptions};
+use crate::pty::TerminalSize;
+use crate::sandbox::Sandbox;
+use crate::spool::Spool;
+use axum::extract::{self, DefaultBodyLimit, Multipart, Query, State};
+use axum::http::{HeaderMap, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::routing::{get, get_service, post};
+use axum::routing::{get_service, on, MethodFilter, MethodRouter};
 use axum::{Json, Router};
 use rusty_runner_api::api::{
-    InfoResponse, OsType, RunRequest, RunResponse, RunScriptQuery, RunStatus, ScriptInterpreter,
-    VERSION,
+    BundleQuery, InfoResponse, JobRecord, JobRequest, JobsQuery, OsType, RunOptions, RunRequest,
+    RunResponse, RunScriptQuery, RunScriptRequest, RunTemplateRequest, ScriptTemplate,
 };
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::process::Command;
+use std::path::PathBuf;
+use std::time::SystemTime;
 use tower_http::services::ServeDir;
 
 // Sanity check that our conditional compilation won't break with weird error messages.
@@ -19,141 +30,693 @@ compile_error!("Unix and Windows are exclusive!");
 #[cfg(not(any(windows, unix)))]
 compile_error!("Either Unix or Windows must be targeted!");
 
+/// The server configuration shared by the routes.
 #[derive(Debug, Clone)]
-struct Config {
-    bash_path: Option<Arc<Path>>,
-    powershell_path: Option<Arc<Path>>,
+pub struct Config {
+    pub interpreters: Interpreters,
+    pub user_policy: UserPolicy,
+    pub sandbox: Sandbox,
+    pub max_output_bytes: Option<usize>,
+    pub history: JobHistory,
+    pub library: ScriptLibrary,
+    pub idempotency: Idempotency,
+    pub active_jobs: ActiveJobs,
+    pub capabilities: Capabilities,
 }
 
+impl Config {
+    /// The effective output limit, the lower of the server and the request limit.
+    fn output_limit(&self, requested: Option<u64>) -> Option<usize> {
+        let requested = requested.map(|limit| usize::try_from(limit).unwrap_or(usize::MAX));
+        match (self.max_output_bytes, requested) {
+            (Some(server), Some(requested)) => Some(server.min(requested)),
+            (server, requested) => server.or(requested),
+        }
+    }
+}
+
+/// Lists the routes by path and method, such that they can be compared to the `OpenAPI` document.
+macro_rules! api_routes {
+    ($(($path:literal, $method:ident, $handler:expr)),* $(,)?) => {
+        vec![$(($path, Method::$method, on(MethodFilter::$method, $handler))),*]
+    };
+}
+
+/// The routes under `/api` handled by the [`Config`], i.e. all but the files served under [`FILE_ROUTE`].
+pub fn api_routes() -> Vec<(&'static str, Method, MethodRouter<Config>)> {
+    api_routes![
+        ("/info", GET, info),
+        ("/run", POST, run_command),
+        ("/runscript", POST, run_script),
+        ("/runscript/json", POST, run_script_json),
+        ("/runscript/bundle", POST, run_script_bundle),
+        ("/scripts", GET, scripts),
+        ("/scripts/:name/run", POST, run_template),
+        ("/jobs", GET, jobs),
+        ("/job/:id", GET, job),
+        ("/openapi.json", GET, openapi::openapi),
+    ]
+}
+
+/// The route under `/api` serving the files of the working directory.
+pub const FILE_ROUTE: &str = "/file";
+
+/// The maximum size of request bodies, e.g. scripts and bundles, as reported by `GET /api/info`.
+pub const MAX_UPLOAD_BYTES: usize = 2 * 1024 * 1024;
+
 /// Routes under `/api`.
-pub fn routes(bash_path: Option<PathBuf>, powershell_path: Option<PathBuf>) -> Router {
-    Router::new()
-        .route("/info", get(info))
-        .route("/run", post(run_command))
-        .route("/runscript", post(run_script))
-        .with_state(Config {
-            bash_path: bash_path.map(Into::into),
-            powershell_path: powershell_path.map(Into::into),
+pub fn routes(config: Config) -> Router {
+    api_routes()
+        .into_iter()
+        .fold(Router::new(), |router, (path, _, handler)| {
+            router.route(path, handler)
         })
-        .nest_service("/file", get_service(ServeDir::new(working_directory())))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(config)
+        .nest_service(FILE_ROUTE, get_service(ServeDir::new(working_directory())))
 }
 
-async fn info() -> Json<InfoResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/info",
+    responses((status = 200, body = InfoResponse)),
+)]
+async fn info(State(config): State<Config>) -> Json<InfoResponse> {
     log::debug!("sending info");
-    Json(InfoResponse {
-        api_version: String::from(VERSION),
-        #[cfg(windows)]
-        computer_name: std::env::var("COMPUTERNAME").unwrap_or(String::from("{unknown}")),
-        #[cfg(windows)]
-        os_type: OsType::Windows,
-        #[cfg(unix)]
-        computer_name: std::env::var("HOSTNAME").unwrap_or(String::from("{unknown}")),
-        #[cfg(unix)]
-        os_type: OsType::Unix,
-    })
+    #[cfg(windows)]
+    let mut info = InfoResponse::new(
+        OsType::Windows,
+        std::env::var("COMPUTERNAME").unwrap_or(String::from("{unknown}")),
+    );
+    #[cfg(unix)]
+    let mut info = InfoResponse::new(
+        OsType::Unix,
+        std::env::var("HOSTNAME").unwrap_or(String::from("{unknown}")),
+    );
+    let capabilities = &config.capabilities;
+    info.server_version = Some(String::from(env!("CARGO_PKG_VERSION")));
+    info.interpreters = capabilities.interpreters().to_vec();
+    info.features = capabilities.features().to_vec();
+    info.limits = capabilities.limits().clone();
+    info.uptime = Some(capabilities.uptime());
+    info.free_disk_bytes = capabilities::free_disk_bytes();
+    Json(info)
 }
 
-async fn run_command(Json(request): Json<RunRequest>) -> Json<RunResponse> {
-    let id = fastrand::u64(..);
+#[utoipa::path(
+    post,
+    path = "/api/run",
+    params(
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Retries with the same key return the original response instead of running again",
+        ),
+    ),
+    request_body = RunRequest,
+    responses(
+        (status = 200, body = RunResponse),
+        (status = 400, body = RunResponse, description = "Rejected request, e.g. for invalid options"),
+        (status = 403, body = RunResponse, description = "Rejected user or group"),
+        (status = 409, body = RunResponse, description = "A job with the same `job_id` is running"),
+    ),
+)]
+async fn run_command(
+    State(config): State<Config>,
+    headers: HeaderMap,
+    Json(request): Json<RunRequest>,
+) -> Response {
+    let job = Job::new(request.job_id.clone(), request.labels.clone());
+    let fingerprint = idempotency::fingerprint(&request, []);
+    let idempotency = config.idempotency.clone();
+    let (status, response) = idempotency
+        .run(
+            &headers,
+            "run",
+            fingerprint,
+            job.clone(),
+            execute_command(config, job, request),
+        )
+        .await;
+    (status, Json(response)).into_response()
+}
 
-    log::info!(id; "received command");
-    log::debug!(id; "command: {}", request.command);
-    log::debug!(id; "arguments: {:?}", request.arguments);
+async fn execute_command(config: Config, job: Job, request: RunRequest) -> Outcome {
+    let started_at = SystemTime::now();
+    let job_request = config
+        .history
+        .is_enabled()
+        .then(|| JobRequest::Run(request.clone()));
 
-    let mut command = Command::new(request.command);
+    log::info!(job; "received command");
+    log::debug!(job; "command: {}", request.command);
+    log::debug!(job; "arguments: {:?}", request.arguments);
+    let _active = match config.active_jobs.register(&job) {
+        Ok(active) => active,
+        Err(reason) => {
+            log::warn!(job; "rejected job id: {reason}");
+            return (StatusCode::CONFLICT, job.failure(reason));
+        }
+    };
+
+    let mut options = match process_options(&config, &job, &request.options) {
+        Ok(options) => options,
+        Err((status, reason)) => return (status, job.failure(reason)),
+    };
+    let mut command =
+        match config
+            .sandbox
+            .command(request.command, &mut options, &working_directory())
+        {
+            Ok(command) => command,
+            Err(reason) => {
+                log::warn!(job; "rejected sandbox: {reason}");
+                return (StatusCode::BAD_REQUEST, job.failure(reason));
+            }
+        };
     command.current_dir(working_directory());
     command.args(request.arguments);
-
-    let response = process(id, command, request.return_stdout, request.return_stderr).await;
-    Json(response)
+    let response = process(&job, command, options).await;
+    record_job(&config.history, started_at, job_request, &response).await;
+    (StatusCode::OK, response)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/runscript",
+    params(
+        RunScriptQuery,
+        RunOptions,
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Retries with the same key return the original response instead of running again",
+        ),
+    ),
+    request_body(content = String, description = "The script", content_type = "text/plain"),
+    responses(
+        (status = 200, body = RunResponse),
+        (status = 400, body = RunResponse, description = "Rejected request, e.g. for invalid options"),
+        (status = 403, body = RunResponse, description = "Rejected user or group"),
+        (status = 409, body = RunResponse, description = "A job with the same `job_id` is running"),
+    ),
+)]
 async fn run_script(
     State(config): State<Config>,
+    headers: HeaderMap,
     Query(query): Query<RunScriptQuery>,
     script: String,
 ) -> Response {
-    let id = fastrand::u64(..);
-    let interpreter = query.interpreter;
-    log::info!(id; "received script");
-    log::debug!(id; "interpreter: {interpreter:?}");
-    log::debug!(id; "script: {script:?}");
-
-    let mut script_path = working_directory();
-    script_path.push(format!("script_{}.{}", id, interpreter.as_extension()));
-    log::debug!(id; "script path: {script_path:?}");
-
-    if let Err(e) = tokio::fs::write(&script_path, &script).await {
-        log::error!(id; "failed to write script data: {e}");
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(failure_response(id, "Failed to write script data")),
+    let job_request = config.history.is_enabled().then(|| JobRequest::RunScript {
+        query: query.clone(),
+        script: script.clone(),
+    });
+    let job = Job::new(query.job_id.clone(), query.labels.clone());
+    let fingerprint = idempotency::fingerprint(&query, [script.as_bytes()]);
+    let request = query.with_script(script);
+    let idempotency = config.idempotency.clone();
+    let (status, response) = idempotency
+        .run(
+            &headers,
+            "runscript",
+            fingerprint,
+            job.clone(),
+            execute_script(config, job, request, None, Vec::new(), job_request),
+        )
+        .await;
+    (status, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/runscript/json",
+    params(
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Retries with the same key return the original response instead of running again",
+        ),
+    ),
+    request_body = RunScriptRequest,
+    responses(
+        (status = 200, body = RunResponse),
+        (status = 400, body = RunResponse, description = "Rejected request, e.g. for invalid options"),
+        (status = 403, body = RunResponse, description = "Rejected user or group"),
+        (status = 409, body = RunResponse, description = "A job with the same `job_id` is running"),
+    ),
+)]
+async fn run_script_json(
+    State(config): State<Config>,
+    headers: HeaderMap,
+    Json(request): Json<RunScriptRequest>,
+) -> Response {
+    let job_request = config
+        .history
+        .is_enabled()
+        .then(|| JobRequest::RunScriptJson(request.clone()));
+    let job = Job::new(request.job_id.clone(), request.labels.clone());
+    let fingerprint = idempotency::fingerprint(&request, []);
+    let idempotency = config.idempotency.clone();
+    let (status, response) = idempotency
+        .run(
+            &headers,
+            "runscript/json",
+            fingerprint,
+            job.clone(),
+            execute_script(config, job, request, None, Vec::new(), job_request),
+        )
+        .await;
+    (status, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/runscript/bundle",
+    params(
+        RunScriptQuery,
+        RunOptions,
+        BundleQuery,
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Retries with the same key return the original response instead of running again",
+        ),
+    ),
+    request_body(
+        content = String,
+        description = "The files of the bundle, each part named by its relative path in the `filename`",
+        content_type = "multipart/form-data",
+    ),
+    responses(
+        (status = 200, body = RunResponse),
+        (status = 400, body = RunResponse, description = "Rejected request, e.g. for invalid options or file names"),
+        (status = 403, body = RunResponse, description = "Rejected user or group"),
+        (status = 409, body = RunResponse, description = "A job with the same `job_id` is running"),
+    ),
+)]
+async fn run_script_bundle(
+    State(config): State<Config>,
+    headers: HeaderMap,
+    Query(query): Query<RunScriptQuery>,
+    Query(bundle_query): Query<BundleQuery>,
+    multipart: Multipart,
+) -> Response {
+    let job = Job::new(query.job_id.clone(), query.labels.clone());
+    let bundle = match Bundle::read(&bundle_query, multipart).await {
+        Ok(bundle) => bundle,
+        Err(reason) => {
+            log::warn!(job; "rejected bundle: {reason}");
+            return (StatusCode::BAD_REQUEST, Json(job.failure(reason))).into_response();
+        }
+    };
+    let fingerprint = idempotency::fingerprint(
+        &(&query, &bundle_query, bundle.file_names()),
+        bundle.contents(),
+    );
+    let job_request = config
+        .history
+        .is_enabled()
+        .then(|| JobRequest::RunScriptBundle {
+            query: query.clone(),
+            bundle: bundle_query,
+            files: bundle.file_names(),
+        });
+    let request = query.with_script(bundle.entrypoint_script());
+    let idempotency = config.idempotency.clone();
+    let (status, response) = idempotency
+        .run(
+            &headers,
+            "runscript/bundle",
+            fingerprint,
+            job.clone(),
+            execute_script(config, job, request, Some(bundle), Vec::new(), job_request),
         )
-            .into_response();
+        .await;
+    (status, Json(response)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/scripts",
+    responses(
+        (status = 200, body = Vec<ScriptTemplate>),
+        (status = 404, description = "Script library not enabled"),
+    ),
+)]
+async fn scripts(State(config): State<Config>) -> Response {
+    log::debug!("sending scripts");
+    if !config.library.is_enabled() {
+        return (StatusCode::NOT_FOUND, "Script library not enabled").into_response();
+    }
+    match config.library.list().await {
+        Ok(templates) => Json(templates).into_response(),
+        Err(e) => {
+            log::error!("failed to read script library: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read script library",
+            )
+                .into_response()
+        }
     }
+}
 
-    // FIXME: test on unix.
-    let mut command = match interpreter {
-        ScriptInterpreter::Bash => {
-            let Some(bash) = config.bash_path else {
-                log::warn!(id; "interpreter {interpreter:?} not configured");
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(failure_response(id, "Bash not supported")),
-                )
-                    .into_response();
-            };
-            let mut command = Command::new(bash.as_ref());
-            command.arg("--");
-            command.arg(&script_path);
-            // `bash -- {file}`.
-            command
-        }
-        ScriptInterpreter::Powershell => {
-            let Some(powershell) = config.powershell_path else {
-                log::warn!(id; "interpreter {interpreter:?} not configured");
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(failure_response(id, "Powershell not supported")),
-                )
-                    .into_response();
-            };
-            let mut command = Command::new(powershell.as_ref());
-            command.arg("-File");
-            command.arg(&script_path);
-            // `powershell -File {file}`.
-            command
-        }
-        ScriptInterpreter::Cmd => {
-            if !cfg!(windows) {
-                log::warn!(id; "Cmd script on unix");
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(failure_response(id, "Cmd not supported on unix")),
-                )
-                    .into_response();
-            }
-            Command::new(script_path.as_os_str())
+#[utoipa::path(
+    post,
+    path = "/api/scripts/{name}/run",
+    params(
+        ("name" = String, Path, description = "The name of the script template"),
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Retries with the same key return the original response instead of running again",
+        ),
+    ),
+    request_body = RunTemplateRequest,
+    responses(
+        (status = 200, body = RunResponse),
+        (status = 400, body = RunResponse, description = "Rejected request, e.g. for unknown or missing parameters"),
+        (status = 403, body = RunResponse, description = "Rejected user or group"),
+        (status = 404, description = "Script library not enabled or template not found"),
+        (status = 409, body = RunResponse, description = "A job with the same `job_id` is running"),
+        (status = 500, body = RunResponse, description = "Invalid script template"),
+    ),
+)]
+async fn run_template(
+    State(config): State<Config>,
+    headers: HeaderMap,
+    extract::Path(name): extract::Path<String>,
+    Json(request): Json<RunTemplateRequest>,
+) -> Response {
+    let job = Job::new(request.job_id.clone(), request.labels.clone());
+    let failure = |status, reason: String| (status, Json(job.failure(reason))).into_response();
+    let template = match config.library.load(&name).await {
+        Ok(Some(template)) => template,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Script template not found").into_response(),
+        Err(reason) => {
+            log::error!(job; "failed to load script template: {reason}");
+            return failure(StatusCode::INTERNAL_SERVER_ERROR, reason);
         }
     };
-    command.current_dir(working_directory());
+    let parameters = match template.bind(&request.parameters) {
+        Ok(parameters) => parameters,
+        Err(reason) => {
+            log::warn!(job; "rejected parameters of script template {name}: {reason}");
+            return failure(StatusCode::BAD_REQUEST, reason);
+        }
+    };
+    let job_request = config
+        .history
+        .is_enabled()
+        .then(|| JobRequest::RunTemplate {
+            name: name.clone(),
+            request: request.clone(),
+        });
+    let fingerprint = idempotency::fingerprint(&(&name, &request), []);
+    let mut script_request = request.with_script(template.info.interpreter, template.script);
+    script_request.arguments = parameters.arguments;
+    script_request.strict |= template.info.strict;
+    let idempotency = config.idempotency.clone();
+    let (status, response) = idempotency
+        .run(
+            &headers,
+            &format!("scripts/{name}/run"),
+            fingerprint,
+            job.clone(),
+            execute_script(
+                config,
+                job,
+                script_request,
+                None,
+                parameters.environment,
+                job_request,
+            ),
+        )
+        .await;
+    (status, Json(response)).into_response()
+}
+
+/// Runs the script of `POST /api/runscript`, `POST /api/runscript/json`, `POST /api/runscript/bundle`
+/// or `POST /api/scripts/{name}/run` with the additional `environment`, recording the original `job_request`.
+async fn execute_script(
+    config: Config,
+    job: Job,
+    request: RunScriptRequest,
+    bundle: Option<Bundle>,
+    environment: Vec<(String, String)>,
+    job_request: Option<JobRequest>,
+) -> Outcome {
+    let started_at = SystemTime::now();
+    let interpreter = &request.interpreter;
+    let script = &request.script;
+    log::info!(job; "received script");
+    log::debug!(job; "interpreter: {interpreter}");
+    log::debug!(job; "script: {script:?}");
+    log::debug!(job; "arguments: {:?}", request.arguments);
+    let _active = match config.active_jobs.register(&job) {
+        Ok(active) => active,
+        Err(reason) => {
+            log::warn!(job; "rejected job id: {reason}");
+            return (StatusCode::CONFLICT, job.failure(reason));
+        }
+    };
+
+    let mut options = match process_options(&config, &job, &request.options) {
+        Ok(options) => options,
+        Err((status, reason)) => return (status, job.failure(reason)),
+    };
+
+    let configured = match config.interpreters.resolve(interpreter, script) {
+        Ok(configured) => configured,
+        Err(reason) => {
+            log::warn!(job; "rejected interpreter {interpreter}: {reason}");
+            return (StatusCode::BAD_REQUEST, job.failure(reason));
+        }
+    };
+
+    let files = ScriptFiles::new(&job, bundle.as_ref(), configured.extension());
+    let script_path = files.script_path();
+    log::debug!(job; "script path: {}", script_path.display());
+    let Some((program, arguments)) =
+        configured.invocation(&script_path, &request.arguments, request.strict)
+    else {
+        log::warn!(job; "rejected strict mode for interpreter {interpreter}");
+        let reason = format!("Interpreter `{interpreter}` has no strict mode");
+        return (StatusCode::BAD_REQUEST, job.failure(reason));
+    };
+    let mut command = match config
+        .sandbox
+        .command(program, &mut options, &files.current_dir())
+    {
+        Ok(command) => command,
+        Err(reason) => {
+            log::warn!(job; "rejected sandbox: {reason}");
+            return (StatusCode::BAD_REQUEST, job.failure(reason));
+        }
+    };
+    command.args(arguments);
+    command.envs(environment);
+    command.current_dir(files.current_dir());
+
+    let direct = configured.executable().is_none();
+    if let Err(e) = files.write(script, direct).await {
+        log::error!(job; "failed to write script data: {e}");
+        files.delete(&job).await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            job.failure("Failed to write script data"),
+        );
+    }
 
     // Run the script
-    let response = process(id, command, query.return_stdout, query.return_stderr).await;
+    let response = process(&job, command, options).await;
+    record_job(&config.history, started_at, job_request, &response).await;
 
     // Delete the script file again
-    if let Err(e) = tokio::fs::remove_file(&script_path).await {
-        log::error!(id; "failed to delete script data: {e}");
+    files.delete(&job).await;
+
+    (StatusCode::OK, response)
+}
+
+/// The [`ProcessOptions`] of the shared request `options`,
+/// rejecting invalid output files and users by the status and reason of the failure.
+fn process_options(
+    config: &Config,
+    job: &Job,
+    options: &RunOptions,
+) -> Result<ProcessOptions, (StatusCode, String)> {
+    let spool = match Spool::new(
+        options.stdout_file.as_deref(),
+        options.stderr_file.as_deref(),
+        options.append_output,
+        options.merge_stderr,
+    ) {
+        Ok(spool) => spool,
+        Err(reason) => {
+            log::warn!(job; "rejected output files: {reason}");
+            return Err((StatusCode::BAD_REQUEST, reason));
+        }
+    };
+    let identity = match config
+        .user_policy
+        .resolve(options.user.as_deref(), options.group.as_deref())
+    {
+        Ok(identity) => identity,
+        Err(reason) => {
+            log::warn!(job; "rejected user: {reason}");
+            return Err((StatusCode::FORBIDDEN, reason));
+        }
+    };
+    Ok(ProcessOptions {
+        return_stdout: options.return_stdout,
+        return_stderr: options.return_stderr,
+        kill_orphans: options.kill_orphans,
+        sandboxed: config.sandbox.enabled(options.sandbox),
+        sandbox_setup: None,
+        tty: options.tty.then_some(TerminalSize {
+            rows: options.tty_rows.unwrap_or(24),
+            columns: options.tty_columns.unwrap_or(80),
+        }),
+        max_output_bytes: config.output_limit(options.max_output_bytes),
+        truncation: options.truncation,
+        output_encoding: options.output_encoding,
+        spool,
+        transcript: options.transcript,
+        identity,
+    })
+}
+
+/// Where the script of a job is written to and run in.
+enum ScriptFiles<'a> {
+    /// A single file `script_{id}.{extension}` run in the working directory.
+    Single(PathBuf),
+    /// The files of a bundle in its own directory, where the entrypoint runs.
+    Bundle(&'a Bundle, PathBuf),
+}
+
+impl<'a> ScriptFiles<'a> {
+    fn new(job: &Job, bundle: Option<&'a Bundle>, extension: &str) -> Self {
+        if let Some(bundle) = bundle {
+            return ScriptFiles::Bundle(bundle, Bundle::dir(job));
+        }
+        let mut script_path = working_directory();
+        script_path.push(format!("script_{}", job.id));
+        script_path.set_extension(extension);
+        ScriptFiles::Single(script_path)
+    }
+
+    fn script_path(&self) -> PathBuf {
+        match self {
+            ScriptFiles::Single(script_path) => script_path.clone(),
+            ScriptFiles::Bundle(bundle, dir) => bundle.entrypoint(dir),
+        }
+    }
+
+    fn current_dir(&self) -> PathBuf {
+        match self {
+            ScriptFiles::Single(_) => working_directory(),
+            ScriptFiles::Bundle(_, dir) => dir.clone(),
+        }
+    }
+
+    /// Writes the script, making it executable if it is run directly.
+    async fn write(&self, script: &str, executable: bool) -> std::io::Result<()> {
+        let script_path = match self {
+            ScriptFiles::Single(script_path) => script_path,
+            ScriptFiles::Bundle(bundle, dir) => return bundle.write(dir, executable).await,
+        };
+        tokio::fs::write(script_path, script).await?;
+        #[cfg(unix)]
+        if executable {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = std::fs::Permissions::from_mode(0o755);
+            tokio::fs::set_permissions(script_path, permissions).await?;
+        }
+        #[cfg(windows)]
+        let _ = executable; // Batch files are executable by their extension.
+        Ok(())
     }
 
-    Json(response).into_response()
+    async fn delete(&self, job: &Job) {
+        match self {
+            ScriptFiles::Single(script_path) => {
+                if let Err(e) = tokio::fs::remove_file(script_path).await {
+                    log::error!(job; "failed to delete script data: {e}");
+                }
+            }
+            ScriptFiles::Bundle(bundle, dir) => bundle.remove(job, dir).await,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    params(JobsQuery),
+    responses(
+        (status = 200, body = Vec<JobRecord>),
+        (status = 404, description = "Job history not enabled"),
+    ),
+)]
+async fn jobs(State(config): State<Config>, Query(query): Query<JobsQuery>) -> Response {
+    log::debug!("sending jobs: {query:?}");
+    if !config.history.is_enabled() {
+        return (StatusCode::NOT_FOUND, "Job history not enabled").into_response();
+    }
+    match config.history.query(&query).await {
+        Ok(jobs) => Json(jobs).into_response(),
+        Err(e) => {
+            log::error!("failed to read job history: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read job history",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}",
+    params((
+        "id" = u64,
+        Path,
+        description = "The runner assigned id of the job. Client chosen `job_id`s are looked up by `GET /api/jobs?job_id=...`",
+    )),
+    responses(
+        (status = 200, body = JobRecord),
+        (status = 404, description = "Job history not enabled or job not found"),
+    ),
+)]
+async fn job(State(config): State<Config>, extract::Path(id): extract::Path<u64>) -> Response {
+    log::debug!(id; "sending job");
+    if !config.history.is_enabled() {
+        return (StatusCode::NOT_FOUND, "Job history not enabled").into_response();
+    }
+    match config.history.get(id).await {
+        Ok(Some(job)) => Json(job).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Job not found").into_response(),
+        Err(e) => {
+            log::error!(id; "failed to read job history: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read job history",
+            )
+                .into_response()
+        }
+    }
 }
 
-fn failure_response(id: u64, reason: impl Into<String>) -> RunResponse {
-    RunResponse {
-        id,
-        status: RunStatus::Failure {
-            reason: reason.into(),
-        },
+/// Records the job in the history, if the request was kept for it.
+async fn record_job(
+    history: &JobHistory,
+    started_at: SystemTime,
+    request: Option<JobRequest>,
+    response: &RunResponse,
+) {
+    if let Some(request) = request {
+        let job = JobRecord::new(response.id, started_at, request, response.status.clone());
+        history.record(job).await;
     }
 }
@@ -0,0 +1,277 @@
+//! Running commands inside linux namespaces by means of [bubblewrap](https://github.com/containers/bubblewrap).
+//!
+//! A sandboxed command runs in new user, mount, pid and network namespaces.
+//! The whole filesystem is mounted read-only, except for the working directory which stays writable.
+
+use crate::process::ProcessOptions;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::process::{Child, Command};
+
+/// The namespaces of the sandbox and the read-only filesystem mounted into it.
+const SANDBOX_ARGS: [&str; 11] = [
+    "--unshare-user",
+    "--unshare-pid",
+    "--unshare-net",
+    "--die-with-parent",
+    "--ro-bind",
+    "/",
+    "/",
+    "--dev",
+    "/dev",
+    "--proc",
+    "/proc",
+];
+
+/// The sandbox configuration of the server.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    bwrap_path: Arc<Path>,
+    default: bool,
+}
+
+impl Sandbox {
+    pub fn new(bwrap_path: &Path, default: bool) -> Self {
+        Self {
+            bwrap_path: bwrap_path.into(),
+            default,
+        }
+    }
+
+    /// Whether sandboxing is supported, i.e. on linux with a `bwrap` that can create the namespaces.
+    ///
+    /// Runs `true` in an empty sandbox, as e.g. disabled user namespaces only fail at that point.
+    pub async fn is_available(&self) -> bool {
+        cfg!(target_os = "linux")
+            && Command::new(self.bwrap_path.as_ref())
+                .args(SANDBOX_ARGS)
+                .args(["--", "true"])
+                .output()
+                .await
+                .is_ok_and(|output| output.status.success())
+    }
+
+    /// Whether a request should be sandboxed, falling back to the server default.
+    pub fn enabled(&self, requested: Option<bool>) -> bool {
+        requested.unwrap_or(self.default)
+    }
+
+    /// Creates a command for `program`, wrapped in the sandbox if [`ProcessOptions::sandboxed`],
+    /// in which case the [`SetupCheck`] is added to the `options`.
+    ///
+    /// Arguments added to the returned command are passed on to `program`.
+    pub fn command(
+        &self,
+        program: impl AsRef<OsStr>,
+        options: &mut ProcessOptions,
+        working_directory: &Path,
+    ) -> Result<Command, String> {
+        if !options.sandboxed {
+            return Ok(Command::new(program));
+        }
+        if !cfg!(target_os = "linux") {
+            return Err(String::from("Sandbox only supported on linux"));
+        }
+        let setup = SetupCheck::new().map_err(|e| format!("Failed to prepare sandbox: {e}"))?;
+        let mut command = Command::new(self.bwrap_path.as_ref());
+        command.arg("--block-fd").arg(setup.fd().to_string());
+        command.args(SANDBOX_ARGS);
+        command
+            .arg("--bind")
+            .arg(working_directory)
+            .arg(working_directory);
+        command.arg("--chdir").arg(working_directory);
+        command.arg("--").arg(program);
+        options.sandbox_setup = Some(setup);
+        Ok(command)
+    }
+}
+
+/// Detects whether `bwrap` set up the sandbox before it started the command.
+///
+/// `bwrap` reads one byte from its `--block-fd` once the sandbox is set up, right before it runs the command,
+/// which doesn't get the pipe. Unlike its exit code and output, this cannot be faked by the command.
+/// Failures to start the command itself, e.g. a missing program, are reported by the command's exit code.
+#[derive(Debug)]
+pub struct SetupCheck {
+    #[cfg(target_os = "linux")]
+    pipe: std::os::fd::OwnedFd,
+}
+
+#[cfg(target_os = "linux")]
+impl SetupCheck {
+    /// Creates the pipe holding the byte, which is only inherited by `bwrap` in [`SetupCheck::spawn`].
+    fn new() -> std::io::Result<Self> {
+        use nix::fcntl::OFlag;
+
+        let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+        nix::unistd::write(&write, &[0])?;
+        Ok(Self { pipe: read })
+    }
+
+    fn fd(&self) -> std::os::fd::RawFd {
+        use std::os::fd::AsRawFd;
+
+        self.pipe.as_raw_fd()
+    }
+
+    /// Spawns the sandboxed command, which inherits the pipe only for the moment of spawning.
+    pub fn spawn(&self, command: &mut Command) -> std::io::Result<Child> {
+        use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+        fcntl(self.fd(), FcntlArg::F_SETFD(FdFlag::empty()))?;
+        let child = command.spawn();
+        fcntl(self.fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        child
+    }
+
+    /// Whether `bwrap` read the byte, i.e. set up the sandbox. Only valid once it exited.
+    pub fn succeeded(&self) -> bool {
+        !matches!(nix::unistd::read(self.fd(), &mut [0]), Ok(1))
+    }
+}
+
+/// Sandboxing is only supported on linux, so there is nothing to check elsewhere.
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+impl SetupCheck {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {})
+    }
+
+    fn fd(&self) -> i32 {
+        -1
+    }
+
+    pub fn spawn(&self, command: &mut Command) -> std::io::Result<Child> {
+        command.spawn()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        true
+    }
+}
+
+/// The reason reported for a sandbox that `bwrap` failed to set up, which is its message on `stderr` if captured.
+pub fn setup_error(stderr: &[u8]) -> String {
+    let message = String::from_utf8_lossy(stderr).trim().to_string();
+    if message.is_empty() {
+        String::from("Failed to set up the sandbox")
+    } else {
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spool::Spool;
+    use std::ffi::OsString;
+
+    fn options(sandboxed: bool) -> ProcessOptions {
+        ProcessOptions {
+            return_stdout: true,
+            return_stderr: true,
+            kill_orphans: false,
+            sandboxed,
+            sandbox_setup: None,
+            tty: None,
+            max_output_bytes: None,
+            truncation: rusty_runner_api::api::OutputTruncation::default(),
+            output_encoding: rusty_runner_api::api::OutputEncoding::default(),
+            spool: Spool::new(None, None, false, false).unwrap(),
+            transcript: false,
+            identity: None,
+        }
+    }
+
+    fn args(command: &Command) -> Vec<OsString> {
+        command
+            .as_std()
+            .get_args()
+            .map(OsStr::to_os_string)
+            .collect()
+    }
+
+    #[test]
+    fn unsandboxed_command() {
+        let sandbox = Sandbox::new(Path::new("bwrap"), false);
+        let mut options = options(false);
+        let command = sandbox
+            .command("echo", &mut options, Path::new("/work"))
+            .unwrap();
+        assert_eq!(command.as_std().get_program(), "echo");
+        assert!(args(&command).is_empty());
+        assert!(options.sandbox_setup.is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sandboxed_command() {
+        let sandbox = Sandbox::new(Path::new("/usr/bin/bwrap"), false);
+        let mut options = options(true);
+        let mut command = sandbox
+            .command("echo", &mut options, Path::new("/work"))
+            .unwrap();
+        command.arg("hi");
+        assert_eq!(command.as_std().get_program(), "/usr/bin/bwrap");
+        let fd = options
+            .sandbox_setup
+            .expect("is sandboxed")
+            .fd()
+            .to_string();
+        let expected = [
+            "--block-fd",
+            &fd,
+            "--unshare-user",
+            "--unshare-pid",
+            "--unshare-net",
+            "--die-with-parent",
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--bind",
+            "/work",
+            "/work",
+            "--chdir",
+            "/work",
+            "--",
+            "echo",
+            "hi",
+        ];
+        assert_eq!(args(&command), expected);
+    }
+
+    /// The check only succeeds if the spawned process read the byte from the inherited pipe.
+    #[cfg(target_os = "linux")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn checks_setup() {
+        let failed = SetupCheck::new().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 1"]);
+        failed.spawn(&mut command).unwrap().wait().await.unwrap();
+        assert!(!failed.succeeded());
+
+        let succeeded = SetupCheck::new().unwrap();
+        let mut command = Command::new("dd");
+        let input = format!("if=/proc/self/fd/{}", succeeded.fd());
+        command.args([input.as_str(), "bs=1", "count=1", "status=none"]);
+        let status = succeeded.spawn(&mut command).unwrap().wait().await.unwrap();
+        assert!(status.success());
+        assert!(succeeded.succeeded());
+    }
+
+    #[test]
+    fn setup_error_message() {
+        assert_eq!(
+            setup_error(b"bwrap: Can't find source path /missing: No such file or directory\n"),
+            "bwrap: Can't find source path /missing: No such file or directory"
+        );
+        assert_eq!(setup_error(b""), "Failed to set up the sandbox");
+    }
+}
@@ -0,0 +1,85 @@
+//! Redirecting command output directly to files in the working directory.
+
+use crate::identity::Identity;
+use crate::process::{contained_path, working_directory};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// The files that `stdout` and `stderr` are written to instead of being captured.
+#[derive(Debug, Clone)]
+pub struct Spool {
+    stdout: Option<PathBuf>,
+    stderr: Option<PathBuf>,
+    append: bool,
+    merge_stderr: bool,
+}
+
+impl Spool {
+    /// Validates the requested files, which must be relative paths inside the working directory.
+    pub fn new(
+        stdout_file: Option<&str>,
+        stderr_file: Option<&str>,
+        append: bool,
+        merge_stderr: bool,
+    ) -> Result<Self, String> {
+        if merge_stderr && (stdout_file.is_none() || stderr_file.is_some()) {
+            return Err(String::from(
+                "merge_stderr requires stdout_file and excludes stderr_file",
+            ));
+        }
+        Ok(Self {
+            stdout: stdout_file.map(resolve).transpose()?,
+            stderr: stderr_file.map(resolve).transpose()?,
+            append,
+            merge_stderr,
+        })
+    }
+
+    /// Redirects the spooled streams of the command to their files, creating parent directories as needed.
+    ///
+    /// The files are owned by the `owner` the command runs as, such that it can read and append to them.
+    pub async fn attach(
+        &self,
+        command: &mut Command,
+        owner: Option<&Identity>,
+    ) -> std::io::Result<()> {
+        if let Some(path) = &self.stdout {
+            let file = self.open(path, owner).await?;
+            if self.merge_stderr {
+                // Share the file handle, such that both streams write to the same offset.
+                command.stderr(file.try_clone()?);
+            }
+            command.stdout(file);
+        }
+        if let Some(path) = &self.stderr {
+            command.stderr(self.open(path, owner).await?);
+        }
+        Ok(())
+    }
+
+    async fn open(&self, path: &Path, owner: Option<&Identity>) -> std::io::Result<std::fs::File> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(path)
+            .await?;
+        let file = file.into_std().await;
+        #[cfg(unix)]
+        if let Some(owner) = owner {
+            std::os::unix::fs::fchown(&file, owner.uid, Some(owner.gid))?;
+        }
+        Ok(file)
+    }
+}
+
+/// Resolves a relative path in the working directory, rejecting anything that could escape it.
+fn resolve(path: &str) -> Result<PathBuf, String> {
+    contained_path(&working_directory(), path).ok_or_else(|| {
+        format!("Output file {path:?} must be a relative path inside the working directory")
+    })
+}
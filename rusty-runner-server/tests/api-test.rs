@@ -1,14 +1,40 @@
 //! Tests which start the binary and call the api.
 
 use httpc_test::Client;
-use rusty_runner_api::api::{InfoResponse, RunResponse, RunStatus};
+use rusty_runner_api::api::{
+    features, BundleQuery, InfoResponse, JobRecord, JobRequest, OutputStream, RunRequest,
+    RunResponse, RunScriptQuery, RunStatus, RunTemplateRequest, ScriptInterpreter,
+};
+use rusty_runner_api::client::{ClientError, RunnerClient};
 use serde_json::json;
+use std::ffi::OsStr;
 use tokio::process::Child;
 
 /// Starts the rusty runner binary and returns a child to abort it and a client to interact with it.
 fn spawn_server() -> anyhow::Result<(Child, Client)> {
-    // IANA recommended port range.
-    let port = fastrand::u16(49152..65535);
+    spawn_server_at(random_port())
+}
+
+/// A random port in the IANA recommended range.
+fn random_port() -> u16 {
+    fastrand::u16(49152..65535)
+}
+
+/// Starts the rusty runner binary on the given port, see [`spawn_server`].
+fn spawn_server_at(port: u16) -> anyhow::Result<(Child, Client)> {
+    spawn_server_with(port, &[])
+}
+
+/// Starts the rusty runner binary with a config file of the given content, see [`spawn_server`].
+fn spawn_server_with_config(config: &str) -> anyhow::Result<(Child, Client)> {
+    let port = random_port();
+    let path = std::env::temp_dir().join(format!("rusty-runner-test-{port}.toml"));
+    std::fs::write(&path, config)?;
+    spawn_server_with(port, &["--config".as_ref(), path.as_os_str()])
+}
+
+/// Starts the rusty runner binary on the given port with additional arguments, see [`spawn_server`].
+fn spawn_server_with(port: u16, args: &[&OsStr]) -> anyhow::Result<(Child, Client)> {
     let child = tokio::process::Command::new(env!("CARGO_BIN_EXE_rusty-runner-server"))
         .kill_on_drop(true)
         .args(["--host", "127.0.0.1"])
@@ -25,6 +51,13 @@ fn spawn_server() -> anyhow::Result<(Child, Client)> {
             "--powershell-path",
             if cfg!(windows) { "powershell" } else { "pwsh" },
         ])
+        .args(["--sh-path", "sh"])
+        .args(["--python-path", "python3"])
+        .args(["--node-path", "node"])
+        .args(["--perl-path", "perl"])
+        .arg("--state-dir")
+        .arg(std::env::temp_dir().join(format!("rusty-runner-test-{port}")))
+        .args(args)
         .spawn()
         .expect("Couldn't spawn server");
     let hc = httpc_test::new_client(format!("http://localhost:{port}"))?;
@@ -39,6 +72,39 @@ async fn info() -> anyhow::Result<()> {
     info.print().await?;
     let info: InfoResponse = info.json_body_as()?;
     assert_eq!(info.api_version, rusty_runner_api::api::VERSION);
+    assert_eq!(
+        info.server_version.as_deref(),
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+    assert!(info.uptime.is_some());
+    let bash = info
+        .interpreter(&ScriptInterpreter::Bash)
+        .expect("bash should be reported");
+    assert!(bash.version.as_deref().is_some_and(|v| v.contains("bash")));
+    assert!(info.supports(features::JOB_HISTORY));
+    assert!(info.supports(features::IDEMPOTENCY));
+    assert!(info.supports(features::SCRIPT_ARGUMENTS));
+    assert!(info.supports(features::SCRIPT_JSON));
+    assert!(info.supports(features::BUNDLES));
+    assert!(info.supports(features::STRICT_MODE));
+    #[cfg(unix)]
+    assert!(info.supports(features::TTY));
+    let max_upload_bytes = info
+        .limits
+        .max_upload_bytes
+        .expect("upload limit should be reported");
+
+    // The reported limit is the one in force.
+    let script = format!("# {}", "x".repeat(usize::try_from(max_upload_bytes)?));
+    let response = hc
+        .do_post("/api/runscript?interpreter=bash", script)
+        .await?;
+    assert_eq!(response.status(), 413);
+    let script = format!("# {}", "x".repeat(usize::try_from(max_upload_bytes)? - 10));
+    let response = hc
+        .do_post("/api/runscript?interpreter=bash", script)
+        .await?;
+    assert_eq!(response.status(), 200);
 
     child.kill().await.expect("Couldn't kill server");
     Ok(())
@@ -82,14 +148,218 @@ async fn bash_echo() -> anyhow::Result<()> {
     let RunStatus::Completed { stdout, .. } = response.status else {
         panic!("Couldn't execute echo");
     };
-    let output =
-        String::from_utf8(stdout.expect("Was configured to return stdout")).expect("is valid utf8");
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
     assert!(output.starts_with("HIIII"));
 
     child.kill().await.expect("Couldn't kill server");
     Ok(())
 }
 
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn other_interpreters() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    for (interpreter, script) in [
+        ("sh", "echo \"$0\""),
+        ("python", "import sys; print(sys.argv[0])"),
+        ("node", "console.log(process.argv[1])"),
+        ("perl", "print \"$0\\n\""),
+    ] {
+        let response = hc
+            .do_post(
+                &format!("/api/runscript?interpreter={interpreter}&return_stdout=true"),
+                script,
+            )
+            .await?;
+        response.print().await?;
+        let response = response.json_body_as::<RunResponse>()?;
+        let RunStatus::Completed { stdout, .. } = response.status else {
+            panic!("Couldn't execute {interpreter}");
+        };
+        let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+            .expect("is valid utf8");
+        // The script is run from its file with the interpreters extension.
+        let extension = match interpreter {
+            "python" => ".py",
+            "node" => ".js",
+            "perl" => ".pl",
+            _ => ".sh",
+        };
+        assert!(
+            output.trim_end().ends_with(extension),
+            "{interpreter}: {output}"
+        );
+    }
+
+    // Interpreters without a configured path are rejected.
+    let response = hc
+        .do_post("/api/runscript?interpreter=ruby", "puts 'hi'")
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn configured_interpreters() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server_with_config(
+        r#"
+        [interpreters.awk]
+        executable = "awk"
+        arguments = ["-f", "{script}", "/dev/null"]
+        extension = "awk"
+
+        # Overrides the builtin, ignoring the script passed as `$0`.
+        [interpreters.sh]
+        executable = "sh"
+        arguments = ["-c", "echo overridden", "{script}"]
+        extension = "txt"
+        "#,
+    )?;
+
+    let info: InfoResponse = hc.do_get("/api/info").await?.json_body_as()?;
+    let custom = ScriptInterpreter::Custom(String::from("awk"));
+    assert!(info.interpreter(&custom).is_some());
+
+    for (interpreter, script, expected) in [
+        (custom, r#"BEGIN { print "awk" }"#, "awk"),
+        (ScriptInterpreter::Sh, "echo builtin", "overridden"),
+    ] {
+        let response = hc
+            .do_post(
+                &format!("/api/runscript?interpreter={interpreter}&return_stdout=true"),
+                script,
+            )
+            .await?;
+        response.print().await?;
+        let response = response.json_body_as::<RunResponse>()?;
+        let stdout = response
+            .status
+            .stdout_str()
+            .expect("Was configured to return stdout");
+        assert_eq!(stdout.trim_end(), expected);
+    }
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn shebang_script() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    for (script, expected) in [
+        ("#!/bin/sh\necho \"sh $0\"", "sh"),
+        (
+            "#!/usr/bin/env python3\nimport sys\nprint('python', sys.argv[0])",
+            "python",
+        ),
+    ] {
+        let response = hc
+            .do_post("/api/runscript?interpreter=auto&return_stdout=true", script)
+            .await?;
+        response.print().await?;
+        let response = response.json_body_as::<RunResponse>()?;
+        let stdout = response
+            .status
+            .stdout_str()
+            .expect("Was configured to return stdout");
+        assert!(stdout.starts_with(expected), "{stdout}");
+        assert!(stdout.contains(&format!("script_{}", response.id)));
+    }
+
+    // The rest of the shebang line is passed as a single argument.
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=auto&return_stdout=true",
+            "#!/bin/sh -eu\nfalse\necho 'not reached'",
+        )
+        .await?
+        .json_body_as::<RunResponse>()?;
+    assert_eq!(response.status.exit_code(), Some(1));
+    assert_eq!(response.status.stdout_str().as_deref(), Some(""));
+
+    let response = hc
+        .do_post("/api/runscript?interpreter=auto", "echo 'no shebang'")
+        .await?;
+    assert_eq!(response.status(), 400);
+    let response = response.json_body_as::<RunResponse>()?;
+    assert!(response
+        .status
+        .failure_reason()
+        .is_some_and(|reason| reason.contains("shebang")));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn invalid_config() -> anyhow::Result<()> {
+    let (child, _hc) = spawn_server_with_config(
+        r#"
+        [interpreters.deno]
+        executable = "deno"
+        arguments = ["run"]
+        extension = "ts"
+        "#,
+    )?;
+    let output = child.wait_with_output().await?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn config_file_options() -> anyhow::Result<()> {
+    // The command line takes precedence over the file.
+    let (mut child, hc) = spawn_server_with_config(
+        r#"
+        bash_path = "no-such-bash"
+        max_output_bytes = "512KiB"
+        allowed_users = ["nobody", "daemon"]
+        "#,
+    )?;
+
+    let info = hc
+        .do_get("/api/info")
+        .await?
+        .json_body_as::<InfoResponse>()?;
+    assert_eq!(info.limits.max_output_bytes, Some(512 * 1024));
+    assert!(info.interpreter(&ScriptInterpreter::Bash).is_some());
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true",
+            "echo ok",
+        )
+        .await?
+        .json_body_as::<RunResponse>()?;
+    assert_eq!(response.status.stdout_str().as_deref(), Some("ok\n"));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn invalid_config_options() -> anyhow::Result<()> {
+    for config in [
+        "no_such_option = 1",
+        "port = [1, 2]",
+        "max_output_bytes = \"lots\"",
+        "config = \"other.toml\"",
+    ] {
+        let (child, _hc) = spawn_server_with_config(config)?;
+        let output = child.wait_with_output().await?;
+        assert!(!output.status.success(), "{config}");
+    }
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn bash_cd() -> anyhow::Result<()> {
     let (mut child, hc) = spawn_server()?;
@@ -108,8 +378,8 @@ async fn bash_cd() -> anyhow::Result<()> {
     let RunStatus::Completed { stdout, .. } = response.status else {
         panic!("Couldn't execute echo");
     };
-    let output =
-        String::from_utf8(stdout.expect("Was configured to return stdout")).expect("is valid utf8");
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
     assert!(output.contains("/bin"));
 
     child.kill().await.expect("Couldn't kill server");
@@ -131,8 +401,8 @@ async fn powershell_echo() -> anyhow::Result<()> {
     let RunStatus::Completed { stdout, .. } = response.status else {
         panic!("Couldn't execute echo");
     };
-    let output =
-        String::from_utf8(stdout.expect("Was configured to return stdout")).expect("is valid utf8");
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
     assert!(output.starts_with("HIIII"));
 
     child.kill().await.expect("Couldn't kill server");
@@ -158,8 +428,8 @@ async fn powershell_cd() -> anyhow::Result<()> {
     let RunStatus::Completed { stdout, .. } = response.status else {
         panic!("Couldn't execute echo");
     };
-    let output =
-        String::from_utf8(stdout.expect("Was configured to return stdout")).expect("is valid utf8");
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
     assert!(output.contains(r"C:\Program Files"));
 
     child.kill().await.expect("Couldn't kill server");
@@ -184,10 +454,1032 @@ async fn cmd_echo() -> anyhow::Result<()> {
     };
     assert_eq!(
         "'HIIII'\r\n",
-        &String::from_utf8(stdout.expect("Was configured to return stdout"))
+        &String::from_utf8(stdout.expect("Was configured to return stdout").into())
             .expect("is valid utf8")
     );
 
     child.kill().await.expect("Couldn't kill server");
     Ok(())
 }
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_signal() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post("/api/runscript?interpreter=bash", "kill -KILL $$")
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed {
+        exit_code, signal, ..
+    } = response.status
+    else {
+        panic!("Couldn't execute kill");
+    };
+    assert_eq!(exit_code, -1001);
+    let signal = signal.expect("Was terminated by a signal");
+    assert_eq!(signal.number, 9);
+    assert_eq!(signal.name, "SIGKILL");
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_kill_orphans() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&kill_orphans=true",
+            r#"
+            sleep 600 &
+            echo "started"
+            "#,
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed {
+        killed_orphans,
+        time_taken,
+        ..
+    } = response.status
+    else {
+        panic!("Couldn't execute script");
+    };
+    assert!(time_taken.as_secs() < 60);
+    assert_eq!(killed_orphans.len(), 1);
+    assert_eq!(killed_orphans[0].name, "sleep");
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_kill_orphans_exit_code() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&kill_orphans=true",
+            r#"
+            sleep 600 &
+            sleep 1
+            echo "done"
+            exit 3
+            "#,
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed {
+        exit_code,
+        signal,
+        stdout,
+        time_taken,
+        ..
+    } = response.status
+    else {
+        panic!("Couldn't execute script");
+    };
+    // The leader itself must not be killed along with its orphans.
+    assert_eq!(exit_code, 3);
+    assert!(signal.is_none());
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
+    assert_eq!(output.trim(), "done");
+    assert!(time_taken.as_secs() < 60);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn disallowed_user() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/run",
+            json!({
+                "command": "whoami",
+                "arguments": [],
+                "user": "root",
+            }),
+        )
+        .await?;
+    response.print().await?;
+    assert_eq!(response.status(), 403);
+    let response: RunResponse = response.json_body_as()?;
+    assert!(matches!(response.status, RunStatus::Failure { .. }));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// Switches to `nobody`, which requires the tests to run as root.
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn allowed_user() -> anyhow::Result<()> {
+    use nix::unistd::{Group, User};
+
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("skipping, switching users requires root");
+        return Ok(());
+    }
+    let Some(nobody) = User::from_name("nobody")? else {
+        eprintln!("skipping, there is no user nobody");
+        return Ok(());
+    };
+    let group = Group::from_gid(nobody.gid)?.expect("primary group exists");
+    let port = random_port();
+    let (mut child, hc) = spawn_server_with(
+        port,
+        &[
+            "--allowed-users".as_ref(),
+            "nobody".as_ref(),
+            "--allowed-groups".as_ref(),
+            group.name.as_ref(),
+        ],
+    )?;
+
+    let response = hc
+        .do_post(
+            "/api/run",
+            json!({
+                "command": "id",
+                "arguments": [],
+                "return_stdout": true,
+                "user": "nobody",
+            }),
+        )
+        .await?;
+    response.print().await?;
+    let response: RunResponse = response.json_body_as()?;
+    let RunStatus::Completed {
+        exit_code, stdout, ..
+    } = response.status
+    else {
+        panic!("Couldn't execute id");
+    };
+    assert_eq!(exit_code, 0);
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
+    assert!(output.starts_with(&format!("uid={}(nobody)", nobody.uid)));
+    assert!(output.contains(&format!("gid={}", nobody.gid)));
+    // The supplementary groups of root must not be kept.
+    let root = Group::from_gid(0.into())?.expect("root group exists");
+    assert!(!output.contains(&format!("{}({})", root.gid, root.name)));
+
+    // Spooled output files belong to the user, which can append to them.
+    let file = format!("nobody_{port}.log");
+    let response: RunResponse = hc
+        .do_post(
+            "/api/run",
+            json!({
+                "command": "sh",
+                "arguments": ["-c", format!("echo spooled; echo appended >> {file}")],
+                "stdout_file": &file,
+                "user": "nobody",
+            }),
+        )
+        .await?
+        .json_body_as()?;
+    assert_eq!(response.status.exit_code(), Some(0), "{response:?}");
+    let path = std::env::temp_dir().join("rusty-runner").join(&file);
+    let owner = std::os::unix::fs::MetadataExt::uid(&std::fs::metadata(&path)?);
+    assert_eq!(owner, nobody.uid.as_raw());
+    assert_eq!(std::fs::read_to_string(&path)?, "spooled\nappended\n");
+
+    // Only switching the group replaces the supplementary groups through the launcher.
+    let response: RunResponse = hc
+        .do_post(
+            "/api/run",
+            json!({
+                "command": "id",
+                "arguments": ["-G"],
+                "return_stdout": true,
+                "group": &group.name,
+            }),
+        )
+        .await?
+        .json_body_as()?;
+    assert_eq!(
+        response.status.stdout_str().as_deref(),
+        Some(format!("{}\n", group.gid).as_str())
+    );
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// The launcher reports failures to set up or execute the command like a shell.
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn launcher_failure() -> anyhow::Result<()> {
+    let launch = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_rusty-runner-server"))
+            .arg("--launch")
+            .args(args)
+            .output()
+    };
+
+    let output = launch(&["--", "/nonexistent/command"])?;
+    assert_eq!(output.status.code(), Some(127));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to run /nonexistent/command"));
+
+    let output = launch(&["--uid", "nobody", "--", "id"])?;
+    assert_eq!(output.status.code(), Some(127));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid id nobody"));
+
+    let output = launch(&["--", "sh", "-c", "exit 5"])?;
+    assert_eq!(output.status.code(), Some(5));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_tty() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&return_stderr=true&tty=true&tty_columns=123",
+            r#"
+            [ -t 1 ] && echo "tty"
+            tput cols
+            echo "error" >&2
+            "#,
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed { stdout, stderr, .. } = response.status else {
+        panic!("Couldn't execute script");
+    };
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
+    assert_eq!(output, "tty\r\n123\r\nerror\r\n");
+    assert!(stderr.is_none_or(|stderr| stderr.is_empty()));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// Whether a working bubblewrap is installed, which can create user namespaces.
+#[cfg(target_os = "linux")]
+fn bwrap_available() -> bool {
+    std::process::Command::new("bwrap")
+        .args(["--unshare-user", "--ro-bind", "/", "/", "--", "true"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_sandbox() -> anyhow::Result<()> {
+    if !bwrap_available() {
+        eprintln!("skipping, bwrap is not available");
+        return Ok(());
+    }
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&sandbox=true",
+            r#"
+            touch /rusty-runner-escape 2>/dev/null && echo "root writable" || echo "root read-only"
+            touch sandboxed.txt && echo "working directory writable" && rm sandboxed.txt
+            "#,
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed {
+        exit_code, stdout, ..
+    } = response.status
+    else {
+        panic!("Couldn't execute sandboxed script");
+    };
+    assert_eq!(exit_code, 0);
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
+    assert!(output.contains("root read-only"), "{output}");
+    assert!(output.contains("working directory writable"), "{output}");
+
+    // Scripts cannot pass off their failures as ones of the sandbox.
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&sandbox=true",
+            "echo 'bwrap: Creating new namespace failed' >&2; exit 1",
+        )
+        .await?
+        .json_body_as::<RunResponse>()?;
+    assert_eq!(response.status.exit_code(), Some(1), "{response:?}");
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// Failures of bwrap itself are detected by it not reading from its `--block-fd`, which is faked here.
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "current_thread")]
+async fn sandbox_setup_error() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let port = random_port();
+    let bwrap = std::env::temp_dir().join(format!("rusty-runner-test-{port}-bwrap"));
+    let (mut child, hc) = spawn_server_with(port, &["--bwrap-path".as_ref(), bwrap.as_os_str()])?;
+    let fake_bwrap = |script: &str| {
+        std::fs::write(&bwrap, format!("#!/bin/sh\n{script}\n"))?;
+        std::fs::set_permissions(&bwrap, std::fs::Permissions::from_mode(0o755))
+    };
+
+    fake_bwrap("echo 'bwrap: Creating new namespace failed' >&2\nexit 1")?;
+    let response = hc
+        .do_post("/api/runscript?interpreter=bash&sandbox=true", "echo hi")
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Failure { reason } = response.status else {
+        panic!("Sandbox failure wasn't detected");
+    };
+    assert_eq!(reason, "bwrap: Creating new namespace failed");
+
+    // Without captured stderr, such failures are still detected.
+    for query in ["tty=true", "stderr_file=err.log"] {
+        let response = hc
+            .do_post(
+                &format!("/api/runscript?interpreter=bash&sandbox=true&{query}"),
+                "echo hi",
+            )
+            .await?
+            .json_body_as::<RunResponse>()?;
+        assert_eq!(
+            response.status.failure_reason(),
+            Some("Failed to set up the sandbox"),
+            "{query}"
+        );
+    }
+
+    // Once bwrap set up the sandbox, the same output and exit code belong to the command.
+    fake_bwrap(
+        "dd if=/proc/self/fd/$2 bs=1 count=1 status=none\n\
+         echo 'bwrap: Creating new namespace failed' >&2\nexit 1",
+    )?;
+    let response = hc
+        .do_post("/api/runscript?interpreter=bash&sandbox=true", "echo hi")
+        .await?
+        .json_body_as::<RunResponse>()?;
+    assert_eq!(response.status.exit_code(), Some(1), "{response:?}");
+
+    child.kill().await.expect("Couldn't kill server");
+    std::fs::remove_file(&bwrap)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_truncated_output() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&max_output_bytes=100",
+            r"
+            printf 'a%.0s' {1..5000}
+            printf 'b%.0s' {1..5000}
+            ",
+        )
+        .await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed {
+        stdout,
+        stdout_total_bytes,
+        ..
+    } = response.status
+    else {
+        panic!("Couldn't execute script");
+    };
+    let output = String::from_utf8(stdout.expect("Was configured to return stdout").into())
+        .expect("is valid utf8");
+    assert_eq!(output, "a".repeat(50) + &"b".repeat(50));
+    assert_eq!(stdout_total_bytes, Some(10000));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_output_encoding() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true&return_stderr=true&output_encoding=utf8_strict",
+            r"
+            echo 'HIIII'
+            printf '\xff' >&2
+            ",
+        )
+        .await?;
+    response.print().await?;
+    let json: serde_json::Value = response.json_body()?;
+    assert_eq!(json["stdout"], json!("HIIII\n"));
+    assert_eq!(json["stderr"], json!({ "base64": "/w==" }));
+    let response: RunResponse = serde_json::from_value(json)?;
+    let RunStatus::Completed { stdout, stderr, .. } = response.status else {
+        panic!("Couldn't execute script");
+    };
+    assert_eq!(stdout.as_deref(), Some(&b"HIIII\n"[..]));
+    assert_eq!(stderr.as_deref(), Some(&b"\xff"[..]));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_spool_output() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let directory = format!("task-{:x}", fastrand::u64(..));
+    let response = hc
+        .do_post(
+            &format!(
+                "/api/runscript?interpreter=bash&return_stdout=true&stdout_file={directory}/out.log&merge_stderr=true"
+            ),
+            r"
+            echo 'out'
+            echo 'err' >&2
+            ",
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed { stdout, .. } = response.status else {
+        panic!("Couldn't execute script");
+    };
+    assert!(stdout.is_none_or(|stdout| stdout.is_empty()));
+
+    let file = hc.do_get(&format!("/api/file/{directory}/out.log")).await?;
+    assert_eq!(file.text_body()?, "out\nerr\n");
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&stdout_file=../escape.log",
+            "echo 'out'",
+        )
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_transcript() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&transcript=true&output_encoding=utf8_lossy",
+            r"
+            echo 'first'
+            sleep 0.2
+            echo 'second' >&2
+            sleep 0.2
+            echo 'third'
+            ",
+        )
+        .await?;
+    response.print().await?;
+    let response = response.json_body_as::<RunResponse>()?;
+    let RunStatus::Completed { transcript, .. } = response.status else {
+        panic!("Couldn't execute script");
+    };
+    let transcript: Vec<_> = transcript
+        .expect("Was configured to return a transcript")
+        .into_iter()
+        .map(|chunk| (chunk.stream, chunk.data.into_bytes()))
+        .collect();
+    assert_eq!(
+        transcript,
+        [
+            (OutputStream::Stdout, b"first\n".to_vec()),
+            (OutputStream::Stderr, b"second\n".to_vec()),
+            (OutputStream::Stdout, b"third\n".to_vec()),
+        ]
+    );
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn job_history() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let response = hc
+        .do_post(
+            "/api/runscript?interpreter=bash&return_stdout=true",
+            "echo 'HIIII'",
+        )
+        .await?;
+    let response = response.json_body_as::<RunResponse>()?;
+
+    let job = hc.do_get(&format!("/api/job/{}", response.id)).await?;
+    job.print().await?;
+    let job: JobRecord = job.json_body_as()?;
+    assert_eq!(job.id, response.id);
+    assert!(matches!(job.request, JobRequest::RunScript { .. }));
+    let RunStatus::Completed { stdout, .. } = job.status else {
+        panic!("Couldn't execute echo");
+    };
+    assert_eq!(stdout.as_deref(), Some(&b"HIIII\n"[..]));
+
+    let jobs = hc.do_get("/api/jobs?status=Completed&limit=1").await?;
+    let jobs: Vec<JobRecord> = jobs.json_body_as()?;
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].id, response.id);
+
+    let missing = hc.do_get("/api/job/1").await?;
+    assert_eq!(missing.status(), 404);
+
+    // Jobs with a client chosen id are found by it, most recent first.
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let response = hc
+            .do_post("/api/runscript?interpreter=bash&job_id=history-42", "true")
+            .await?;
+        ids.push(response.json_body_as::<RunResponse>()?.id);
+    }
+    let jobs = hc.do_get("/api/jobs?job_id=history-42").await?;
+    let jobs: Vec<JobRecord> = jobs.json_body_as()?;
+    let found: Vec<_> = jobs.iter().map(|job| job.id).collect();
+    assert_eq!(found, [ids[1], ids[0]]);
+    assert_eq!(jobs[0].job_id(), Some("history-42"));
+    let jobs = hc.do_get("/api/jobs?limit=2").await?;
+    let jobs: Vec<JobRecord> = jobs.json_body_as()?;
+    assert_eq!(jobs.len(), 2);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_idempotency_key() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, hc) = spawn_server_at(port)?;
+
+    let counter = format!("idempotency_{port}.txt");
+    let send = |key: &'static str| {
+        hc.reqwest_client()
+            .post(format!(
+                "http://localhost:{port}/api/runscript?interpreter=bash&return_stdout=true"
+            ))
+            .header("Idempotency-Key", key)
+            .body(format!(
+                "sleep 0.5; echo run >> {counter}; wc -l < {counter}"
+            ))
+            .send()
+    };
+
+    // A concurrent retry attaches to the running job, a later one replays its response.
+    let (first, concurrent) = tokio::join!(send("deploy-1"), send("deploy-1"));
+    let first: RunResponse = first?.json().await?;
+    let concurrent: RunResponse = concurrent?.json().await?;
+    let later: RunResponse = send("deploy-1").await?.json().await?;
+    assert_eq!(first.id, concurrent.id);
+    assert_eq!(first.id, later.id);
+    let RunStatus::Completed { stdout, .. } = later.status else {
+        panic!("Couldn't execute script");
+    };
+    assert_eq!(stdout.as_deref().map(<[u8]>::trim_ascii), Some(&b"1"[..]));
+
+    // Another key runs again.
+    let other: RunResponse = send("deploy-2").await?.json().await?;
+    assert_ne!(first.id, other.id);
+    let RunStatus::Completed { stdout, .. } = other.status else {
+        panic!("Couldn't execute script");
+    };
+    assert_eq!(stdout.as_deref().map(<[u8]>::trim_ascii), Some(&b"2"[..]));
+
+    // Reusing a key for a different request is rejected.
+    let response = hc
+        .reqwest_client()
+        .post(format!("http://localhost:{port}/api/runscript?interpreter=bash"))
+        .header("Idempotency-Key", "deploy-1")
+        .body("echo different")
+        .send()
+        .await?;
+    assert_eq!(response.status(), 422);
+    let response: RunResponse = response.json().await?;
+    assert_ne!(response.id, first.id);
+    assert_ne!(response.id, 0);
+    assert!(matches!(response.status, RunStatus::Failure { .. }));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_job_id_and_labels() -> anyhow::Result<()> {
+    let (mut child, hc) = spawn_server()?;
+
+    let run = |script: &'static str| {
+        hc.do_post(
+            "/api/runscript?interpreter=bash&job_id=deploy-42&labels=pipeline=ci,sha=abc123",
+            script,
+        )
+    };
+    let duplicate = async {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        run("true").await
+    };
+    let (first, duplicate) = tokio::join!(run("sleep 1"), duplicate);
+
+    let first = first?.json_body_as::<RunResponse>()?;
+    assert_eq!(first.job_id.as_deref(), Some("deploy-42"));
+    assert_eq!(first.labels["pipeline"], "ci");
+    assert_eq!(first.labels["sha"], "abc123");
+    assert!(matches!(first.status, RunStatus::Completed { .. }));
+
+    let duplicate = duplicate?;
+    assert_eq!(duplicate.status(), 409);
+    let duplicate = duplicate.json_body_as::<RunResponse>()?;
+    assert!(matches!(duplicate.status, RunStatus::Failure { .. }));
+
+    // The id can be reused once the job finished.
+    let response = hc
+        .do_post(
+            "/api/run",
+            json!({
+                "command": "true",
+                "arguments": [],
+                "job_id": "deploy-42",
+                "labels": { "owner": "ops" },
+            }),
+        )
+        .await?
+        .json_body_as::<RunResponse>()?;
+    assert_eq!(response.job_id.as_deref(), Some("deploy-42"));
+    assert_eq!(response.labels["owner"], "ops");
+    assert!(matches!(response.status, RunStatus::Completed { .. }));
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn typed_client() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, _hc) = spawn_server_at(port)?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let info = client.info().await?;
+    assert_eq!(info.api_version, rusty_runner_api::api::VERSION);
+
+    let request = RunRequest::new("echo")
+        .args(["Hello", "World"])
+        .capture_stdout();
+    let status = client.run(&request, None).await?.status;
+    assert!(status.success());
+    assert_eq!(status.stdout_str().as_deref(), Some("Hello World\n"));
+
+    // A retry with the same key replays the first response.
+    let first = client.run(&request, Some("typed-client")).await?;
+    let retry = client.run(&request, Some("typed-client")).await?;
+    assert_eq!(first.id, retry.id);
+
+    let file = format!("client_{port}/out.txt");
+    let query = RunScriptQuery::new(ScriptInterpreter::Bash);
+    let response = client
+        .run_script(
+            &query,
+            format!("mkdir -p client_{port}; echo HIIII > {file}"),
+            None,
+        )
+        .await?;
+    assert!(matches!(
+        response.status,
+        RunStatus::Completed { exit_code: 0, .. }
+    ));
+    assert_eq!(client.fetch_file(&file).await?, b"HIIII\n");
+
+    let missing = client.fetch_file("does/not/exist.txt").await;
+    assert!(matches!(
+        missing,
+        Err(ClientError::Status { status: 404, .. })
+    ));
+
+    // Rejected requests are returned as failures.
+    let query = RunScriptQuery::new(ScriptInterpreter::Cmd);
+    let response = client.run_script(&query, "echo HIIII", None).await?;
+    assert!(response.status.failure_reason().is_some());
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_script_arguments() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, _hc) = spawn_server_at(port)?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let script = r#"printf '%s|' "$@""#;
+    let arguments = ["plain", "with space", "'quoted'", "--flag", "a,b"];
+    let query = RunScriptQuery::new(ScriptInterpreter::Bash)
+        .args(arguments)
+        .capture_stdout();
+    let expected = Some("plain|with space|'quoted'|--flag|a,b|");
+
+    let status = client.run_script(&query, script, None).await?.status;
+    assert_eq!(status.stdout_str().as_deref(), expected);
+
+    let status = client
+        .run_script_json(&query.with_script(script), None)
+        .await?
+        .status;
+    assert_eq!(status.stdout_str().as_deref(), expected);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_strict_script() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, hc) = spawn_server_with(
+        port,
+        &["--bash-flags".as_ref(), "--norc --noprofile".as_ref()],
+    )?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let script = r#"false | true; echo "piped $1""#;
+    let query = RunScriptQuery::new(ScriptInterpreter::Bash)
+        .arg("on")
+        .capture_stdout();
+    let status = client.run_script(&query, script, None).await?.status;
+    assert_eq!(status.exit_code(), Some(0));
+    assert_eq!(status.stdout_str().as_deref(), Some("piped on\n"));
+
+    let status = client
+        .run_script(&query.clone().strict(), script, None)
+        .await?
+        .status;
+    assert_eq!(status.exit_code(), Some(1));
+    assert_eq!(status.stdout_str().as_deref(), Some(""));
+
+    let status = client
+        .run_script_json(
+            &query.strict().with_script(r#"echo "$UNSET_VARIABLE""#),
+            None,
+        )
+        .await?
+        .status;
+    assert_ne!(status.exit_code(), Some(0));
+
+    let response = hc
+        .do_post("/api/runscript?interpreter=python&strict=true", "print(1)")
+        .await?;
+    assert_eq!(response.status(), 400);
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn bash_bundle() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, _hc) = spawn_server_at(port)?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let files = [
+        (
+            "main.sh",
+            "source lib/greet.sh\ngreet \"$1\" > greeting.txt\ncat greeting.txt",
+        ),
+        ("lib/greet.sh", "greet() { echo \"Hello $1\"; }"),
+    ];
+    let query = RunScriptQuery::new(ScriptInterpreter::Bash)
+        .arg("World")
+        .capture_stdout();
+
+    // The bundle is removed afterwards by default.
+    let response = client
+        .run_script_bundle(&query, &BundleQuery::new("main.sh"), files, None)
+        .await?;
+    assert_eq!(
+        response.status.stdout_str().as_deref(),
+        Some("Hello World\n")
+    );
+    let removed = client
+        .fetch_file(&format!("bundle_{}/greeting.txt", response.id))
+        .await;
+    assert!(matches!(
+        removed,
+        Err(ClientError::Status { status: 404, .. })
+    ));
+
+    let bundle = BundleQuery::new("main.sh").keep_bundle();
+    let response = client
+        .run_script_bundle(&query, &bundle, files, None)
+        .await?;
+    let kept = client
+        .fetch_file(&format!("bundle_{}/greeting.txt", response.id))
+        .await?;
+    assert_eq!(kept, b"Hello World\n");
+
+    // Files must stay inside the bundle and contain the entrypoint.
+    for (entrypoint, name) in [("main.sh", "../escape.sh"), ("missing.sh", "main.sh")] {
+        let response = client
+            .run_script_bundle(
+                &query,
+                &BundleQuery::new(entrypoint),
+                [(name, "echo")],
+                None,
+            )
+            .await?;
+        assert!(response.status.failure_reason().is_some());
+        assert_ne!(response.id, 0);
+    }
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// The sandbox runs a bundle in its own directory, so relative paths resolve against it.
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "current_thread")]
+async fn bash_bundle_sandbox() -> anyhow::Result<()> {
+    if !bwrap_available() {
+        eprintln!("skipping, bwrap is not available");
+        return Ok(());
+    }
+    let port = random_port();
+    let (mut child, _hc) = spawn_server_at(port)?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let files = [
+        ("main.sh", "source lib/greet.sh\ngreet \"$1\""),
+        ("lib/greet.sh", "greet() { echo \"Hello $1\"; }"),
+    ];
+    let query = RunScriptQuery::new(ScriptInterpreter::Bash)
+        .arg("Sandbox")
+        .sandbox(true)
+        .capture_stdout();
+    let response = client
+        .run_script_bundle(&query, &BundleQuery::new("main.sh"), files, None)
+        .await?;
+    assert_eq!(
+        response.status.stdout_str().as_deref(),
+        Some("Hello Sandbox\n"),
+        "{response:?}"
+    );
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+/// Every path and method in the `OpenAPI` document must be routed,
+/// i.e. respond with something else than the empty 404 or 405 of the router.
+#[tokio::test(flavor = "current_thread")]
+async fn openapi_in_sync() -> anyhow::Result<()> {
+    let port = random_port();
+    let (mut child, hc) = spawn_server_at(port)?;
+
+    let file = format!("openapi_{port}.txt");
+    hc.do_post(
+        "/api/run",
+        json!({ "command": "touch", "arguments": [&file] }),
+    )
+    .await?;
+
+    let spec: serde_json::Value = hc.do_get("/api/openapi.json").await?.json_body()?;
+    assert_eq!(spec["info"]["version"], rusty_runner_api::api::VERSION);
+    for schema in [
+        "InfoResponse",
+        "RunRequest",
+        "RunScriptQuery",
+        "RunResponse",
+        "RunStatus",
+    ] {
+        assert!(
+            spec["components"]["schemas"].get(schema).is_some(),
+            "Missing schema {schema}"
+        );
+    }
+
+    // The flattened options are listed as query parameters of their own.
+    let parameters = spec["paths"]["/api/runscript"]["post"]["parameters"]
+        .as_array()
+        .expect("parameters is an array");
+    assert!(parameters.iter().any(|p| p["name"] == "return_stdout"));
+    assert!(!parameters.iter().any(|p| p["name"] == "options"));
+
+    let paths = spec["paths"].as_object().expect("paths is an object");
+    assert!(paths.len() >= 7, "Missing paths: {paths:?}");
+    for (path, operations) in paths {
+        let url = format!(
+            "http://localhost:{port}{}",
+            path.replace("{path}", &file).replace("{id}", "1")
+        );
+        for method in operations
+            .as_object()
+            .expect("path item is an object")
+            .keys()
+        {
+            let method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes())?;
+            let response = hc
+                .reqwest_client()
+                .request(method.clone(), &url)
+                .send()
+                .await?;
+            let status = response.status();
+            let body = response.bytes().await?;
+            assert_ne!(status, 405, "{method} {path} not routed");
+            assert!(
+                status != 404 || !body.is_empty(),
+                "{method} {path} not routed"
+            );
+        }
+    }
+
+    child.kill().await.expect("Couldn't kill server");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn script_templates() -> anyhow::Result<()> {
+    let port = random_port();
+    let dir = std::env::temp_dir().join(format!("rusty-runner-test-scripts-{port}"));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join("greet.toml"),
+        r#"
+description = "Greets someone"
+interpreter = "bash"
+script = 'echo "$GREETING $1"'
+
+[[parameters]]
+name = "GREETING"
+default = "Hello"
+
+[[parameters]]
+name = "name"
+pass_as = "argument"
+"#,
+    )?;
+    std::fs::write(dir.join("broken.toml"), "interpreter = 42")?;
+    let (mut child, _hc) = spawn_server_with(port, &["--scripts-dir".as_ref(), dir.as_os_str()])?;
+    let client = RunnerClient::new(&format!("http://localhost:{port}"))?;
+
+    let info = client.info().await?;
+    assert!(info.supports(features::SCRIPT_LIBRARY));
+
+    let templates = client.scripts().await?;
+    assert_eq!(templates.len(), 1, "{templates:?}");
+    assert_eq!(templates[0].name, "greet");
+    assert_eq!(templates[0].interpreter, ScriptInterpreter::Bash);
+    assert_eq!(templates[0].parameters.len(), 2);
+
+    let request = RunTemplateRequest::new()
+        .param("name", "World; rm -rf /")
+        .capture_stdout();
+    let response = client.run_template("greet", &request, None).await?;
+    assert_eq!(
+        response.status.stdout_str().as_deref(),
+        Some("Hello World; rm -rf /\n")
+    );
+    let response = client
+        .run_template("greet", &request.clone().param("GREETING", "Hi"), None)
+        .await?;
+    assert_eq!(
+        response.status.stdout_str().as_deref(),
+        Some("Hi World; rm -rf /\n")
+    );
+
+    for rejected in [RunTemplateRequest::new(), request.param("unknown", "value")] {
+        let response = client.run_template("greet", &rejected, None).await?;
+        assert!(matches!(response.status, RunStatus::Failure { .. }));
+        assert_ne!(response.id, 0);
+    }
+    let missing = client
+        .run_template("missing", &RunTemplateRequest::new(), None)
+        .await;
+    assert!(matches!(
+        missing,
+        Err(ClientError::Status { status: 404, .. })
+    ));
+
+    child.kill().await.expect("Couldn't kill server");
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -3,7 +3,8 @@
 //! and deserializable rust structs.
 
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -15,15 +16,36 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// {
 ///    "os_type": "Unix",
 ///    "computer_name": "GLaDOS",
-///    "api_version": "2.0.0"
+///    "api_version": "3.0.0",
+///    "server_version": "0.4.2",
+///    "interpreters": [
+///        {
+///            "interpreter": "bash",
+///            "version": "GNU bash, version 5.2.15(1)-release (x86_64-pc-linux-gnu)"
+///        }
+///    ],
+///    "features": ["kill_orphans", "tty", "transcript"],
+///    "limits": {
+///        "max_upload_bytes": 2097152
+///    },
+///    "uptime": {
+///        "secs": 3600,
+///        "nanos": 0
+///    },
+///    "free_disk_bytes": 52428800000
 /// }
 /// # "#;
 /// # let deser: rusty_runner_api::api::InfoResponse
 /// #    = serde_json::from_str(ser).expect("failed parsing");
 /// # assert_eq!(deser.computer_name, "GLaDOS");
 /// # assert_eq!(deser.api_version, rusty_runner_api::api::VERSION);
+/// # assert!(deser.supports(rusty_runner_api::api::features::TTY));
+/// # assert!(deser.interpreter(&rusty_runner_api::api::ScriptInterpreter::Bash).is_some());
 /// ```
+/// Older runners only return the first three fields.
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
 pub struct InfoResponse {
     /// The operating system type running.
     pub os_type: OsType,
@@ -31,10 +53,133 @@ pub struct InfoResponse {
     pub computer_name: String,
     /// The version of the api supported. Defined by [`VERSION`].
     pub api_version: String,
+    /// The version of the runner implementation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    /// The interpreters configured for `POST /api/runscript`.
+    #[serde(default)]
+    pub interpreters: Vec<InterpreterInfo>,
+    /// The optional [`features`] supported by the runner.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// The limits the runner imposes on requests.
+    #[serde(default)]
+    pub limits: RunnerLimits,
+    /// The time since the runner started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schema(value_type = Option<DurationSchema>))]
+    pub uptime: Option<Duration>,
+    /// The free disk space available to the working directory in bytes, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_disk_bytes: Option<u64>,
+}
+
+impl InfoResponse {
+    /// The info of a runner supporting the current [`VERSION`].
+    #[must_use]
+    pub fn new(os_type: OsType, computer_name: impl Into<String>) -> Self {
+        Self {
+            os_type,
+            computer_name: computer_name.into(),
+            api_version: String::from(VERSION),
+            server_version: None,
+            interpreters: Vec::new(),
+            features: Vec::new(),
+            limits: RunnerLimits::default(),
+            uptime: None,
+            free_disk_bytes: None,
+        }
+    }
+
+    /// `true` if the runner supports the optional feature, see [`features`].
+    #[must_use]
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|supported| supported == feature)
+    }
+
+    /// The info of the interpreter, if configured on the runner.
+    #[must_use]
+    pub fn interpreter(&self, interpreter: &ScriptInterpreter) -> Option<&InterpreterInfo> {
+        self.interpreters
+            .iter()
+            .find(|info| info.interpreter == *interpreter)
+    }
+}
+
+/// The names of optional features in [`InfoResponse::features`].
+///
+/// Runners may report further features unknown to this version of the api.
+pub mod features {
+    /// Killing orphaned processes with `kill_orphans`.
+    pub const KILL_ORPHANS: &str = "kill_orphans";
+    /// Pseudo terminals with `tty`.
+    pub const TTY: &str = "tty";
+    /// Sandboxing with `sandbox`.
+    pub const SANDBOX: &str = "sandbox";
+    /// Switching the unix `user` and `group`.
+    pub const USER_SWITCHING: &str = "user_switching";
+    /// Transcripts with `transcript`.
+    pub const TRANSCRIPT: &str = "transcript";
+    /// Writing output to files with `stdout_file` and `stderr_file`.
+    pub const OUTPUT_FILES: &str = "output_files";
+    /// The `Idempotency-Key` header.
+    pub const IDEMPOTENCY: &str = "idempotency";
+    /// The job history at `GET /api/jobs` and `GET /api/job/{id}`.
+    pub const JOB_HISTORY: &str = "job_history";
+    /// The `OpenAPI` document at `GET /api/openapi.json`.
+    pub const OPENAPI: &str = "openapi";
+    /// Running scripts by their shebang with [`ScriptInterpreter::Auto`](super::ScriptInterpreter::Auto).
+    pub const SHEBANG: &str = "shebang";
+    /// The script templates at `GET /api/scripts` and `POST /api/scripts/{name}/run`.
+    pub const SCRIPT_LIBRARY: &str = "script_library";
+    /// Passing `arguments` to scripts.
+    pub const SCRIPT_ARGUMENTS: &str = "script_arguments";
+    /// Running scripts sent as a json [`RunScriptRequest`](super::RunScriptRequest) at `POST /api/runscript`.
+    pub const SCRIPT_JSON: &str = "script_json";
+    /// Running multi-file bundles at `POST /api/runscript/bundle`.
+    pub const BUNDLES: &str = "bundles";
+    /// Running scripts in strict mode with `strict`.
+    pub const STRICT_MODE: &str = "strict_mode";
+}
+
+/// An interpreter configured on the runner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct InterpreterInfo {
+    pub interpreter: ScriptInterpreter,
+    /// The version reported by the interpreter, if it could be determined.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl InterpreterInfo {
+    #[must_use]
+    pub fn new(interpreter: ScriptInterpreter, version: Option<String>) -> Self {
+        Self {
+            interpreter,
+            version,
+        }
+    }
+}
+
+/// The limits a runner imposes on requests, where `None` means unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct RunnerLimits {
+    /// The maximum size of a request body, e.g. a script.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_upload_bytes: Option<u64>,
+    /// The maximum number of bytes captured per output stream, see `max_output_bytes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<u64>,
 }
 
 /// The OS type as given by `#[cfg(windows)]` and `#[cfg(unix)]`.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
 pub enum OsType {
     Windows,
     Unix,
@@ -59,7 +204,9 @@ pub enum OsType {
 /// #    = serde_json::from_str(ser).expect("failed parsing");
 /// # assert_eq!(deser.command, "echo");
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
 pub struct RunRequest {
     /// The command as available on the path or a path to an executable.
     pub command: String,
@@ -70,12 +217,316 @@ pub struct RunRequest {
     /// are not supported.
     /// Avoid `cmd.exe /C`!
     pub arguments: Vec<String>,
+    /// The [`RunOptions`], flattened into this request.
+    #[serde(flatten)]
+    pub options: RunOptions,
+    /// A client chosen id of the job, which is echoed in the response and logged by the runner.
+    ///
+    /// Jobs with the same id cannot run at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Arbitrary labels of the job, e.g. the pipeline or commit, which are echoed in the response
+    /// and logged by the runner.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+}
+
+/// The options shared by [`RunRequest`], [`RunScriptQuery`], [`RunScriptRequest`] and [`RunTemplateRequest`],
+/// which flatten them into their own fields.
+///
+/// ```
+/// # use rusty_runner_api::api::RunRequest;
+/// let request: RunRequest =
+///     serde_json::from_str(r#"{"command": "echo", "arguments": [], "return_stdout": true}"#)?;
+/// assert!(request.options.return_stdout);
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "schema", into_params(parameter_in = Query))]
+#[non_exhaustive]
+pub struct RunOptions {
     /// `true` if the api should capture and return `stdout`. Defaults to `false`.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
     pub return_stdout: bool,
     /// `true` if the api should capture and return `stderr`. Defaults to `false`.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
     pub return_stderr: bool,
+    /// `true` if the api should kill all processes remaining in the command's process group
+    /// once the command exits or the request is cancelled. Defaults to `false`.
+    ///
+    /// Only supported on unix, where each command runs in its own process group.
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
+    pub kill_orphans: bool,
+    /// The unix user to run the command as. Defaults to the user of the runner.
+    ///
+    /// The user must be allowed by the runner. `HOME` and `USER` are set accordingly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// The unix group to run the command as. Defaults to the primary group of `user`.
+    ///
+    /// The group must be allowed by the runner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// `true` if the command should run in a sandbox of new user, mount, pid and network namespaces,
+    /// where only the working directory is writable. Defaults to the runner's configuration.
+    ///
+    /// Only supported on linux. Sandbox setup errors are reported as [`RunStatus::Failure`],
+    /// which requires `stderr` to be captured, so `tty` and a spooled `stderr` are rejected.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "flattened_query::deserialize_option"
+    )]
+    pub sandbox: Option<bool>,
+    /// `true` if the command should run attached to a pseudo terminal. Defaults to `false`.
+    ///
+    /// The merged terminal output is returned as `stdout` if `return_stdout` is set, `stderr` is not returned.
+    /// Note that terminals translate newlines to `\r\n`. `stdin` is not attached.
+    /// Only supported on unix.
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
+    pub tty: bool,
+    /// The number of rows of the pseudo terminal if `tty` is set. Defaults to 24.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "flattened_query::deserialize_option"
+    )]
+    pub tty_rows: Option<u16>,
+    /// The number of columns of the pseudo terminal if `tty` is set. Defaults to 80.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "flattened_query::deserialize_option"
+    )]
+    pub tty_columns: Option<u16>,
+    /// The maximum number of bytes captured per output stream.
+    /// The runner may impose a lower limit. Defaults to no limit.
+    ///
+    /// Output beyond the limit is discarded according to `truncation`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "flattened_query::deserialize_option"
+    )]
+    pub max_output_bytes: Option<u64>,
+    /// Which part of the output to keep if it exceeds `max_output_bytes`. Defaults to head and tail.
+    #[serde(default)]
+    pub truncation: OutputTruncation,
+    /// How `stdout` and `stderr` are encoded in the response. Defaults to [`OutputEncoding::Bytes`].
+    #[serde(default)]
+    pub output_encoding: OutputEncoding,
+    /// A file to write `stdout` to instead of capturing it, relative to the working directory.
+    ///
+    /// Parent directories are created. The file can be fetched by `GET /api/file/{path}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_file: Option<String>,
+    /// A file to write `stderr` to instead of capturing it, relative to the working directory.
+    ///
+    /// Parent directories are created. The file can be fetched by `GET /api/file/{path}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_file: Option<String>,
+    /// `true` if `stdout_file` and `stderr_file` should be appended to instead of overwritten.
+    /// Defaults to `false`.
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
+    pub append_output: bool,
+    /// `true` if `stderr` should be written to `stdout_file` as well. Defaults to `false`.
+    ///
+    /// Requires `stdout_file` and excludes `stderr_file`.
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
+    pub merge_stderr: bool,
+    /// `true` if the api should return a [`transcript`](TranscriptChunk) of `stdout` and `stderr`
+    /// in the order the chunks were read. Defaults to `false`.
+    ///
+    /// Note that commands commonly buffer their output when not writing to a terminal.
+    /// Only the first `max_output_bytes` of each stream are recorded.
+    #[serde(default, deserialize_with = "flattened_query::deserialize")]
+    pub transcript: bool,
+}
+
+/// Generates the builder methods for the options shared by [`RunRequest`], [`RunScriptQuery`], [`RunScriptRequest`]
+/// and [`RunTemplateRequest`].
+macro_rules! shared_builder_methods {
+    () => {
+        /// Captures and returns `stdout`.
+        #[must_use]
+        pub fn capture_stdout(mut self) -> Self {
+            self.options.return_stdout = true;
+            self
+        }
+
+        /// Captures and returns `stderr`.
+        #[must_use]
+        pub fn capture_stderr(mut self) -> Self {
+            self.options.return_stderr = true;
+            self
+        }
+
+        /// Kills the processes remaining in the process group once the command exits.
+        #[must_use]
+        pub fn kill_orphans(mut self) -> Self {
+            self.options.kill_orphans = true;
+            self
+        }
+
+        /// Runs the command as the given unix user.
+        #[must_use]
+        pub fn user(mut self, user: impl Into<String>) -> Self {
+            self.options.user = Some(user.into());
+            self
+        }
+
+        /// Runs the command as the given unix group.
+        #[must_use]
+        pub fn group(mut self, group: impl Into<String>) -> Self {
+            self.options.group = Some(group.into());
+            self
+        }
+
+        /// Enables or disables the sandbox, overriding the runner's default.
+        #[must_use]
+        pub fn sandbox(mut self, sandbox: bool) -> Self {
+            self.options.sandbox = Some(sandbox);
+            self
+        }
+
+        /// Attaches a pseudo terminal of the default size.
+        #[must_use]
+        pub fn tty(mut self) -> Self {
+            self.options.tty = true;
+            self
+        }
+
+        /// Attaches a pseudo terminal of the given size.
+        #[must_use]
+        pub fn tty_size(mut self, rows: u16, columns: u16) -> Self {
+            self.options.tty = true;
+            self.options.tty_rows = Some(rows);
+            self.options.tty_columns = Some(columns);
+            self
+        }
+
+        /// Limits the captured bytes per output stream, truncated according to `truncation`.
+        #[must_use]
+        pub fn max_output_bytes(mut self, limit: u64, truncation: OutputTruncation) -> Self {
+            self.options.max_output_bytes = Some(limit);
+            self.options.truncation = truncation;
+            self
+        }
+
+        /// Encodes the returned output as given.
+        #[must_use]
+        pub fn output_encoding(mut self, encoding: OutputEncoding) -> Self {
+            self.options.output_encoding = encoding;
+            self
+        }
+
+        /// Writes `stdout` to a file relative to the working directory instead of capturing it.
+        #[must_use]
+        pub fn stdout_file(mut self, path: impl Into<String>) -> Self {
+            self.options.stdout_file = Some(path.into());
+            self
+        }
+
+        /// Writes `stderr` to a file relative to the working directory instead of capturing it.
+        #[must_use]
+        pub fn stderr_file(mut self, path: impl Into<String>) -> Self {
+            self.options.stderr_file = Some(path.into());
+            self
+        }
+
+        /// Appends to the output files instead of overwriting them.
+        #[must_use]
+        pub fn append_output(mut self) -> Self {
+            self.options.append_output = true;
+            self
+        }
+
+        /// Writes `stderr` to the `stdout_file` as well.
+        #[must_use]
+        pub fn merge_stderr(mut self) -> Self {
+            self.options.merge_stderr = true;
+            self
+        }
+
+        /// Returns a transcript interleaving `stdout` and `stderr`.
+        #[must_use]
+        pub fn transcript(mut self) -> Self {
+            self.options.transcript = true;
+            self
+        }
+
+        /// Sets the client chosen id of the job.
+        #[must_use]
+        pub fn job_id(mut self, job_id: impl Into<String>) -> Self {
+            self.job_id = Some(job_id.into());
+            self
+        }
+
+        /// Adds a label to the job.
+        #[must_use]
+        pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+            self.labels.insert(key.into(), value.into());
+            self
+        }
+    };
+}
+
+/// Generates the builder methods for the `arguments` of [`RunRequest`], [`RunScriptQuery`] and [`RunScriptRequest`].
+macro_rules! argument_builder_methods {
+    () => {
+        /// Adds an argument.
+        #[must_use]
+        pub fn arg(mut self, argument: impl Into<String>) -> Self {
+            self.arguments.push(argument.into());
+            self
+        }
+
+        /// Adds multiple arguments.
+        #[must_use]
+        pub fn args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, arguments: I) -> Self {
+            self.arguments.extend(arguments.into_iter().map(Into::into));
+            self
+        }
+    };
+}
+
+/// The builder methods specific to scripts.
+macro_rules! script_builder_methods {
+    () => {
+        /// Runs the script in strict mode, stopping at the first failing command.
+        #[must_use]
+        pub fn strict(mut self) -> Self {
+            self.strict = true;
+            self
+        }
+    };
+}
+
+impl RunRequest {
+    /// A request to run the `command` without arguments, capturing no output.
+    ///
+    /// ```
+    /// # use rusty_runner_api::api::RunRequest;
+    /// let request = RunRequest::new("echo")
+    ///     .arg("Hello")
+    ///     .arg("World")
+    ///     .capture_stdout();
+    /// # assert_eq!(request.arguments, ["Hello", "World"]);
+    /// ```
+    #[must_use]
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            arguments: Vec::new(),
+            options: RunOptions::default(),
+            job_id: None,
+            labels: HashMap::new(),
+        }
+    }
+
+    shared_builder_methods!();
+    argument_builder_methods!();
 }
 
 /// The query schema for `POST /api/runscript`.
@@ -88,109 +539,1416 @@ pub struct RunRequest {
 /// # let deser: rusty_runner_api::api::RunScriptQuery
 /// #    = serde_urlencoded::from_str(ser.trim()).expect("failed parsing");
 /// # assert!(matches!(deser.interpreter, rusty_runner_api::api::ScriptInterpreter::Bash));
+/// # assert!(deser.options.return_stderr);
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
+/// With a job id and labels:
+/// ```
+/// # let ser = r#"
+/// interpreter=bash&job_id=deploy-42&labels=pipeline%3Dci%2Csha%3Dabc123
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunScriptQuery
+/// #    = serde_urlencoded::from_str(ser.trim()).expect("failed parsing");
+/// # assert_eq!(deser.job_id.as_deref(), Some("deploy-42"));
+/// # assert_eq!(deser.labels["sha"], "abc123");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "schema", into_params(parameter_in = Query))]
+#[non_exhaustive]
 pub struct RunScriptQuery {
     /// The script in the request body will be run by the given `interpreter`.
     pub interpreter: ScriptInterpreter,
-    // Note, `serde` does not support proper flattening here, so this cannot be moved to a struct `OutputOptions`,
-    // <https://github.com/nox/serde_urlencoded/issues/33>.
-    /// `true` if the api should capture and return `stdout`. Defaults to `false`.
+    /// The arguments passed to the script after its path, e.g. `$1` in bash.
+    ///
+    /// Given as a json array, e.g. `arguments=["--verbose","a b"]`.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "arguments_query"
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schema(value_type = String),
+        param(value_type = String)
+    )]
+    pub arguments: Vec<String>,
+    /// `true` if the script should stop at the first failing command. Defaults to `false`.
+    ///
+    /// For example `-euo pipefail` in bash or `$ErrorActionPreference = 'Stop'` with `Set-StrictMode` in PowerShell.
+    /// Requests for interpreters without a strict mode fail with `400 Bad Request`.
     #[serde(default)]
-    pub return_stdout: bool,
-    /// `true` if the api should capture and return `stderr`. Defaults to `false`.
+    pub strict: bool,
+    /// The [`RunOptions`], flattened into this request.
+    #[serde(flatten)]
+    #[cfg_attr(feature = "schema", param(ignore))]
+    pub options: RunOptions,
+    /// A client chosen id of the job, which is echoed in the response and logged by the runner.
+    ///
+    /// Jobs with the same id cannot run at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Arbitrary labels of the job, e.g. the pipeline or commit, which are echoed in the response
+    /// and logged by the runner.
+    ///
+    /// Given as comma separated `key=value` pairs, e.g. `labels=pipeline=ci,sha=abc123`,
+    /// so keys cannot contain `=` and neither keys nor values can contain `,`. Serializing such labels fails.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        with = "labels_query"
+    )]
+    #[cfg_attr(
+        feature = "schema",
+        schema(value_type = String),
+        param(value_type = String)
+    )]
+    pub labels: HashMap<String, String>,
+}
+
+impl RunScriptQuery {
+    /// A query to run the script with the `interpreter`, capturing no output.
+    ///
+    /// ```
+    /// # use rusty_runner_api::api::{RunScriptQuery, ScriptInterpreter};
+    /// let query = RunScriptQuery::new(ScriptInterpreter::Bash)
+    ///     .capture_stdout()
+    ///     .label("pipeline", "ci");
+    /// # assert!(query.options.return_stdout);
+    /// ```
+    #[must_use]
+    pub fn new(interpreter: ScriptInterpreter) -> Self {
+        Self {
+            interpreter,
+            arguments: Vec::new(),
+            strict: false,
+            options: RunOptions::default(),
+            job_id: None,
+            labels: HashMap::new(),
+        }
+    }
+
+    /// The equivalent json body for `POST /api/runscript/json`.
+    ///
+    /// ```
+    /// # use rusty_runner_api::api::{RunScriptQuery, ScriptInterpreter};
+    /// let request = RunScriptQuery::new(ScriptInterpreter::Bash)
+    ///     .arg("World")
+    ///     .with_script(r#"echo "Hello $1""#);
+    /// # assert_eq!(request.arguments, ["World"]);
+    /// ```
+    #[must_use]
+    pub fn with_script(self, script: impl Into<String>) -> RunScriptRequest {
+        RunScriptRequest {
+            interpreter: self.interpreter,
+            script: script.into(),
+            arguments: self.arguments,
+            strict: self.strict,
+            options: self.options,
+            job_id: self.job_id,
+            labels: self.labels,
+        }
+    }
+
+    shared_builder_methods!();
+    argument_builder_methods!();
+    script_builder_methods!();
+}
+
+/// The json-body schema for `POST /api/runscript/json`,
+/// the alternative to [`RunScriptQuery`] with the script in the body.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// {
+///   "interpreter": "bash",
+///   "script": "echo \"Hello $1\"",
+///   "arguments": ["World"],
+///   "return_stdout": true
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunScriptRequest
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(deser.arguments, ["World"]);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct RunScriptRequest {
+    /// The interpreter the `script` will be run by.
+    pub interpreter: ScriptInterpreter,
+    /// The content of the script.
+    pub script: String,
+    /// The arguments passed to the script after its path, e.g. `$1` in bash. Defaults to none.
     #[serde(default)]
-    pub return_stderr: bool,
+    pub arguments: Vec<String>,
+    /// `true` if the script should stop at the first failing command. Defaults to `false`.
+    ///
+    /// See [`RunScriptQuery::strict`].
+    #[serde(default)]
+    pub strict: bool,
+    /// The [`RunOptions`], flattened into this request.
+    #[serde(flatten)]
+    pub options: RunOptions,
+    /// A client chosen id of the job, which is echoed in the response and logged by the runner.
+    ///
+    /// Jobs with the same id cannot run at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Arbitrary labels of the job, e.g. the pipeline or commit, which are echoed in the response
+    /// and logged by the runner.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
 }
 
-/// The interpreter that the script will be called with.
+impl RunScriptRequest {
+    /// A request to run the `script` with the `interpreter`, capturing no output.
+    #[must_use]
+    pub fn new(interpreter: ScriptInterpreter, script: impl Into<String>) -> Self {
+        RunScriptQuery::new(interpreter).with_script(script)
+    }
+
+    shared_builder_methods!();
+    argument_builder_methods!();
+    script_builder_methods!();
+}
+
+/// The additional query schema for `POST /api/runscript/bundle`, next to the [`RunScriptQuery`].
 ///
-/// Not all interpreters may be supported by any runner.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum ScriptInterpreter {
-    Bash,
-    /// Cmd.exe is outdated and powershell should be preferred.
-    Cmd,
-    Powershell,
+/// The body is `multipart/form-data`, where each part is a file of the bundle,
+/// named by the relative path in its `filename`, e.g. `lib/helpers.sh`.
+/// The bundle is written to a fresh directory `bundle_{id}` in the working directory,
+/// where `id` is the [`RunResponse::id`], and the entrypoint runs in this directory.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// interpreter=bash&entrypoint=main.sh&keep_bundle=true
+/// # "#;
+/// # let deser: rusty_runner_api::api::BundleQuery
+/// #    = serde_urlencoded::from_str(ser.trim()).expect("failed parsing");
+/// # assert_eq!(deser.entrypoint, "main.sh");
+/// # assert!(deser.keep_bundle);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "schema", into_params(parameter_in = Query))]
+#[non_exhaustive]
+pub struct BundleQuery {
+    /// The file of the bundle run by the `interpreter`, relative to the bundle directory.
+    pub entrypoint: String,
+    /// `true` if the bundle directory should be kept after the script ran,
+    /// e.g. to fetch files it created. Defaults to `false`.
+    #[serde(default)]
+    pub keep_bundle: bool,
 }
 
-impl ScriptInterpreter {
-    /// Returns the default file extension.
+impl BundleQuery {
+    /// A query running the `entrypoint` of the bundle, which is removed afterwards.
     #[must_use]
-    pub fn as_extension(&self) -> &'static str {
-        match self {
-            ScriptInterpreter::Bash => "sh",
-            ScriptInterpreter::Cmd => "bat",
-            ScriptInterpreter::Powershell => "ps1",
+    pub fn new(entrypoint: impl Into<String>) -> Self {
+        Self {
+            entrypoint: entrypoint.into(),
+            keep_bundle: false,
         }
     }
+
+    /// Keeps the bundle directory after the script ran.
+    #[must_use]
+    pub fn keep_bundle(mut self) -> Self {
+        self.keep_bundle = true;
+        self
+    }
 }
 
-/// The json response format for `/api/run` and `/api/runscript`.
+/// A script template of the runner's library, as listed by `GET /api/scripts`.
 ///
-/// # Serialized Examples
-/// A completed command:
+/// Templates are run by name with `POST /api/scripts/{name}/run`, see [`RunTemplateRequest`].
+///
+/// # Serialized Example
 /// ```
 /// # let ser = r#"
 /// {
-///     "id": 73001,
-///     "status": "Completed",
-///     "exit_code": 1,
-///     "time_taken": {
-///         "secs": 21,
-///         "nanos": 800000
-///     }
+///     "name": "rotate-logs",
+///     "description": "Compresses old logs",
+///     "interpreter": "bash",
+///     "parameters": [
+///         { "name": "LOG_DIR", "pass_as": "env" },
+///         { "name": "days", "default": "7", "pass_as": "argument" }
+///     ]
 /// }
 /// # "#;
-/// # let deser: rusty_runner_api::api::RunResponse
+/// # let deser: rusty_runner_api::api::ScriptTemplate
 /// #    = serde_json::from_str(ser).expect("failed parsing");
-/// # assert!(matches!(deser.status, rusty_runner_api::api::RunStatus::Completed { .. }));
+/// # assert_eq!(deser.parameters[1].default.as_deref(), Some("7"));
 /// ```
-/// A command that could not be executed:
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct ScriptTemplate {
+    /// The name of the template, i.e. its file name without the `.toml` extension.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The interpreter the template is run by.
+    pub interpreter: ScriptInterpreter,
+    /// `true` if the template always runs in strict mode, see [`RunScriptQuery::strict`].
+    #[serde(default)]
+    pub strict: bool,
+    /// The parameters of the template, in the order they are passed as arguments.
+    #[serde(default)]
+    pub parameters: Vec<TemplateParameter>,
+}
+
+impl ScriptTemplate {
+    /// A template without description and parameters.
+    #[must_use]
+    pub fn new(name: impl Into<String>, interpreter: ScriptInterpreter) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            interpreter,
+            strict: false,
+            parameters: Vec::new(),
+        }
+    }
+}
+
+/// A parameter of a [`ScriptTemplate`].
+///
+/// Values are only ever passed as environment variables or arguments, never substituted into the script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct TemplateParameter {
+    /// The name of the parameter, which is also the name of the environment variable.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The value if the request does not set the parameter. The parameter is required if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// How the value is passed to the script. Defaults to [`ParameterPassing::Env`].
+    #[serde(default)]
+    pub pass_as: ParameterPassing,
+}
+
+/// How a [`TemplateParameter`] is passed to the script.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterPassing {
+    /// As an environment variable named like the parameter.
+    #[default]
+    Env,
+    /// As an argument after the script path, in the order of the parameters.
+    Argument,
+}
+
+/// The json-body schema for `POST /api/scripts/{name}/run`.
+///
+/// The interpreter and the script are taken from the [`ScriptTemplate`].
+///
+/// # Serialized Example
 /// ```
 /// # let ser = r#"
 /// {
-///     "id": 1234567890,
-///     "status": "Failure",
-///     "reason": "Not supported"
+///     "parameters": { "LOG_DIR": "/var/log/app", "days": "30" },
+///     "return_stdout": true
 /// }
 /// # "#;
-/// # let deser: rusty_runner_api::api::RunResponse
+/// # let deser: rusty_runner_api::api::RunTemplateRequest
 /// #    = serde_json::from_str(ser).expect("failed parsing");
-/// # assert!(matches!(deser.status, rusty_runner_api::api::RunStatus::Failure { .. }));
+/// # assert_eq!(deser.parameters["days"], "30");
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RunResponse {
-    pub id: u64,
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct RunTemplateRequest {
+    /// The values of the template's parameters by name. Unknown parameters are rejected.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub parameters: HashMap<String, String>,
+    /// `true` if the script should stop at the first failing command. Defaults to the template.
+    ///
+    /// See [`RunScriptQuery::strict`].
+    #[serde(default)]
+    pub strict: bool,
+    /// The [`RunOptions`], flattened into this request.
     #[serde(flatten)]
-    pub status: RunStatus,
+    pub options: RunOptions,
+    /// A client chosen id of the job, which is echoed in the response and logged by the runner.
+    ///
+    /// Jobs with the same id cannot run at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Arbitrary labels of the job, e.g. the pipeline or commit, which are echoed in the response
+    /// and logged by the runner.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
 }
 
-/// The outcome of a command.
+impl RunTemplateRequest {
+    /// A request to run a template without parameters, capturing no output.
+    ///
+    /// ```
+    /// # use rusty_runner_api::api::RunTemplateRequest;
+    /// let request = RunTemplateRequest::new()
+    ///     .param("days", "30")
+    ///     .capture_stdout();
+    /// # assert_eq!(request.parameters["days"], "30");
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of a parameter.
+    #[must_use]
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parameters.insert(name.into(), value.into());
+        self
+    }
+
+    /// The equivalent [`RunScriptRequest`] running the `script` of a template with the `interpreter`,
+    /// without the parameters.
+    #[must_use]
+    pub fn with_script(
+        self,
+        interpreter: ScriptInterpreter,
+        script: impl Into<String>,
+    ) -> RunScriptRequest {
+        RunScriptRequest {
+            interpreter,
+            script: script.into(),
+            arguments: Vec::new(),
+            strict: self.strict,
+            options: self.options,
+            job_id: self.job_id,
+            labels: self.labels,
+        }
+    }
+
+    shared_builder_methods!();
+    script_builder_methods!();
+}
+
+/// Deserializes the non-string fields of [`RunOptions`] from strings as well,
+/// as query strings pass flattened fields as such, <https://github.com/nox/serde_urlencoded/issues/33>.
+mod flattened_query {
+    use serde::{de::Error, Deserialize, Deserializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value<T> {
+        Native(T),
+        Text(String),
+    }
+
+    impl<T: FromStr<Err: Display>> Value<T> {
+        fn parse<E: Error>(self) -> Result<T, E> {
+            match self {
+                Value::Native(value) => Ok(value),
+                Value::Text(text) => text
+                    .parse()
+                    .map_err(|e| E::custom(format!("invalid value {text:?}: {e}"))),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + FromStr<Err: Display>,
+    {
+        Value::deserialize(deserializer)?.parse()
+    }
+
+    pub fn deserialize_option<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + FromStr<Err: Display>,
+    {
+        Option::<Value<T>>::deserialize(deserializer)?
+            .map(Value::parse)
+            .transpose()
+    }
+}
+
+/// (De)serializes arguments as a json array, as query strings don't support sequences.
+mod arguments_query {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        arguments: &[String],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let json = serde_json::to_string(arguments).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&json)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json).map_err(|e| {
+            serde::de::Error::custom(format!(
+                "arguments {json:?} are not a json array of strings: {e}"
+            ))
+        })
+    }
+}
+
+/// (De)serializes labels as comma separated `key=value` pairs, as query strings don't support maps.
 ///
-/// If the command could be started, then this is a [`Completed`](RunStatus::Completed)
+/// Keys containing `,` or `=` and values containing `,` cannot be represented and fail to serialize.
+mod labels_query {
+    use serde::{ser::Error, Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        labels: &HashMap<String, String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut pairs = labels
+            .iter()
+            .map(|(key, value)| {
+                if key.contains([',', '=']) || value.contains(',') {
+                    return Err(S::Error::custom(format!(
+                        "label {key:?}={value:?} cannot be passed in a query, \
+                         as keys must not contain `,` or `=` and values must not contain `,`"
+                    )));
+                }
+                Ok(format!("{key}={value}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        pairs.sort_unstable();
+        serializer.serialize_str(&pairs.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, String>, D::Error> {
+        String::deserialize(deserializer)?
+            .split(',')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').ok_or_else(|| {
+                    serde::de::Error::custom(format!("label {pair:?} is not a key=value pair"))
+                })?;
+                Ok((key.to_string(), value.to_string()))
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::api::RunScriptQuery;
+        use crate::api::ScriptInterpreter;
+
+        fn query(key: &str, value: &str) -> RunScriptQuery {
+            RunScriptQuery::new(ScriptInterpreter::Bash).label(key, value)
+        }
+
+        #[test]
+        fn roundtrips_labels() {
+            let query = query("sha", "a=b").label("pipeline", "ci");
+            let ser = serde_urlencoded::to_string(&query).unwrap();
+            let deser: RunScriptQuery = serde_urlencoded::from_str(&ser).unwrap();
+            assert_eq!(deser.labels, query.labels);
+        }
+
+        #[test]
+        fn rejects_ambiguous_labels() {
+            for (key, value) in [("a,b", "c"), ("a=b", "c"), ("a", "b,c")] {
+                let result = serde_urlencoded::to_string(query(key, value));
+                assert!(result.is_err(), "{key}={value}");
+            }
+        }
+    }
+}
+
+/// The part of an output stream to keep when it is truncated.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OutputTruncation {
+    /// Keep the beginning of the output.
+    Head,
+    /// Keep the end of the output.
+    Tail,
+    /// Keep half of the limit from the beginning and half from the end.
+    #[default]
+    HeadAndTail,
+}
+
+/// The json encoding of `stdout` and `stderr` in a [`RunStatus::Completed`].
+///
+/// Regardless of the encoding, [`OutputBytes`] deserializes to the raw bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum OutputEncoding {
+    /// An array of numbers, e.g. `[72, 105]`. Roughly 4 times the size of the raw data.
+    #[default]
+    Bytes,
+    /// A base64 encoded string in an object, e.g. `{"base64": "SGk="}`.
+    Base64,
+    /// A string, where invalid utf8 is replaced by `U+FFFD`, e.g. `"Hi"`.
+    /// This may lose information.
+    Utf8Lossy,
+    /// A string if the output is valid utf8, otherwise falls back to [`Base64`](OutputEncoding::Base64).
+    Utf8Strict,
+}
+
+/// Captured output that is serialized according to its [`OutputEncoding`].
+///
+/// Dereferences to the raw bytes.
+///
+/// # Serialized Examples
+/// As [`OutputEncoding::Bytes`]:
+/// ```
+/// # let ser = r#"
+/// [72, 105]
+/// # "#;
+/// # let deser: rusty_runner_api::api::OutputBytes
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(&*deser, b"Hi");
+/// ```
+/// As [`OutputEncoding::Base64`]:
+/// ```
+/// # let ser = r#"
+/// {"base64": "SGk="}
+/// # "#;
+/// # let deser: rusty_runner_api::api::OutputBytes
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(&*deser, b"Hi");
+/// ```
+/// As [`OutputEncoding::Utf8Lossy`] or [`OutputEncoding::Utf8Strict`]:
+/// ```
+/// # let ser = r#"
+/// "Hi"
+/// # "#;
+/// # let deser: rusty_runner_api::api::OutputBytes
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(&*deser, b"Hi");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputBytes {
+    bytes: Vec<u8>,
+    encoding: OutputEncoding,
+}
+
+impl OutputBytes {
+    /// Wraps the raw bytes to be serialized with the given encoding.
+    #[must_use]
+    pub fn new(bytes: Vec<u8>, encoding: OutputEncoding) -> Self {
+        Self { bytes, encoding }
+    }
+
+    /// The encoding used for serialization.
+    ///
+    /// After deserialization, this is the encoding that was detected.
+    #[must_use]
+    pub fn encoding(&self) -> OutputEncoding {
+        self.encoding
+    }
+
+    /// Returns the raw bytes.
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl std::ops::Deref for OutputBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl From<OutputBytes> for Vec<u8> {
+    fn from(output: OutputBytes) -> Self {
+        output.bytes
+    }
+}
+
+/// The serialized forms of [`OutputBytes`], distinguishable by their json type.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum EncodedOutput<'a> {
+    Bytes(std::borrow::Cow<'a, [u8]>),
+    Utf8(std::borrow::Cow<'a, str>),
+    Base64 { base64: String },
+}
+
+impl Serialize for OutputBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+
+        let base64 = || EncodedOutput::Base64 {
+            base64: base64::engine::general_purpose::STANDARD.encode(&self.bytes),
+        };
+        let encoded = match self.encoding {
+            OutputEncoding::Bytes => EncodedOutput::Bytes((&self.bytes).into()),
+            OutputEncoding::Base64 => base64(),
+            OutputEncoding::Utf8Lossy => EncodedOutput::Utf8(String::from_utf8_lossy(&self.bytes)),
+            OutputEncoding::Utf8Strict => match std::str::from_utf8(&self.bytes) {
+                Ok(text) => EncodedOutput::Utf8(text.into()),
+                Err(_) => base64(),
+            },
+        };
+        encoded.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use base64::Engine;
+
+        Ok(match EncodedOutput::deserialize(deserializer)? {
+            EncodedOutput::Bytes(bytes) => Self::new(bytes.into_owned(), OutputEncoding::Bytes),
+            EncodedOutput::Utf8(text) => {
+                Self::new(text.into_owned().into_bytes(), OutputEncoding::Utf8Strict)
+            }
+            EncodedOutput::Base64 { base64 } => Self::new(
+                base64::engine::general_purpose::STANDARD
+                    .decode(base64)
+                    .map_err(serde::de::Error::custom)?,
+                OutputEncoding::Base64,
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "schema")]
+impl utoipa::PartialSchema for OutputBytes {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{ArrayBuilder, ObjectBuilder, OneOfBuilder, Type};
+
+        OneOfBuilder::new()
+            .description(Some(
+                "The output encoded according to the requested `output_encoding`.",
+            ))
+            .item(ArrayBuilder::new().items(ObjectBuilder::new().schema_type(Type::Integer)))
+            .item(ObjectBuilder::new().schema_type(Type::String))
+            .item(
+                ObjectBuilder::new()
+                    .property("base64", ObjectBuilder::new().schema_type(Type::String))
+                    .required("base64"),
+            )
+            .into()
+    }
+}
+
+#[cfg(feature = "schema")]
+impl utoipa::ToSchema for OutputBytes {}
+
+/// The serialized form of a [`Duration`] for the `OpenAPI` schema.
+#[cfg(feature = "schema")]
+#[derive(utoipa::ToSchema)]
+#[schema(as = Duration)]
+#[allow(dead_code)]
+struct DurationSchema {
+    secs: u64,
+    nanos: u32,
+}
+
+/// The serialized form of a [`SystemTime`] for the `OpenAPI` schema.
+#[cfg(feature = "schema")]
+#[derive(utoipa::ToSchema)]
+#[schema(as = SystemTime)]
+#[allow(dead_code)]
+struct SystemTimeSchema {
+    secs_since_epoch: u64,
+    nanos_since_epoch: u32,
+}
+
+/// The interpreter that the script will be called with.
+///
+/// Not all interpreters may be supported by any runner.
+/// Runners may configure further interpreters, which are addressed by name with [`ScriptInterpreter::Custom`].
+///
+/// ```
+/// # use rusty_runner_api::api::ScriptInterpreter;
+/// assert_eq!("python".parse(), Ok(ScriptInterpreter::Python));
+/// assert_eq!("deno".parse(), Ok(ScriptInterpreter::Custom(String::from("deno"))));
+/// assert!("no spaces".parse::<ScriptInterpreter>().is_err());
+/// # let deser: ScriptInterpreter = serde_json::from_str(r#""deno""#).expect("failed parsing");
+/// # assert_eq!(deser.name(), "deno");
+/// # assert_eq!(serde_json::to_string(&deser).expect("failed serializing"), r#""deno""#);
+/// # assert!(serde_json::from_str::<ScriptInterpreter>(r#""no spaces""#).is_err());
+/// # assert!(serde_json::from_str::<ScriptInterpreter>(r#""""#).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[non_exhaustive]
+pub enum ScriptInterpreter {
+    Bash,
+    /// Cmd.exe is outdated and powershell should be preferred.
+    Cmd,
+    Powershell,
+    /// The POSIX shell.
+    Sh,
+    Zsh,
+    Fish,
+    Python,
+    /// Node.js.
+    Node,
+    Perl,
+    Ruby,
+    /// Runs the script by its shebang line, e.g. `#!/usr/bin/env python3`.
+    ///
+    /// Runners on unix run the interpreter of the shebang with the script like the kernel would,
+    /// such that the script needs not be executable.
+    /// Runners on windows resolve the named interpreter from their configured interpreters,
+    /// ignoring a trailing version, i.e. `python3` falls back to `python`.
+    /// Scripts without a shebang are rejected.
+    Auto,
+    /// An interpreter configured on the runner by name,
+    /// consisting of ascii alphanumerics, `-`, `_` and `.`.
+    Custom(String),
+}
+
+impl ScriptInterpreter {
+    /// The builtin interpreters, i.e. all but [`ScriptInterpreter::Custom`].
+    pub const BUILTIN: [ScriptInterpreter; 11] = [
+        ScriptInterpreter::Bash,
+        ScriptInterpreter::Cmd,
+        ScriptInterpreter::Powershell,
+        ScriptInterpreter::Sh,
+        ScriptInterpreter::Zsh,
+        ScriptInterpreter::Fish,
+        ScriptInterpreter::Python,
+        ScriptInterpreter::Node,
+        ScriptInterpreter::Perl,
+        ScriptInterpreter::Ruby,
+        ScriptInterpreter::Auto,
+    ];
+
+    /// Returns the default file extension.
+    ///
+    /// Custom interpreters define their extension on the runner and automatic ones by the shebang,
+    /// so `txt` is returned for them.
+    #[must_use]
+    pub fn as_extension(&self) -> &'static str {
+        match self {
+            ScriptInterpreter::Bash => "sh",
+            ScriptInterpreter::Cmd => "bat",
+            ScriptInterpreter::Powershell => "ps1",
+            ScriptInterpreter::Sh => "sh",
+            ScriptInterpreter::Zsh => "zsh",
+            ScriptInterpreter::Fish => "fish",
+            ScriptInterpreter::Python => "py",
+            ScriptInterpreter::Node => "js",
+            ScriptInterpreter::Perl => "pl",
+            ScriptInterpreter::Ruby => "rb",
+            ScriptInterpreter::Auto | ScriptInterpreter::Custom(_) => "txt",
+        }
+    }
+
+    /// The name as used in the api, e.g. `bash`.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            ScriptInterpreter::Bash => "bash",
+            ScriptInterpreter::Cmd => "cmd",
+            ScriptInterpreter::Powershell => "powershell",
+            ScriptInterpreter::Sh => "sh",
+            ScriptInterpreter::Zsh => "zsh",
+            ScriptInterpreter::Fish => "fish",
+            ScriptInterpreter::Python => "python",
+            ScriptInterpreter::Node => "node",
+            ScriptInterpreter::Perl => "perl",
+            ScriptInterpreter::Ruby => "ruby",
+            ScriptInterpreter::Auto => "auto",
+            ScriptInterpreter::Custom(name) => name,
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl utoipa::PartialSchema for ScriptInterpreter {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{ObjectBuilder, OneOfBuilder, Type};
+
+        OneOfBuilder::new()
+            .description(Some(
+                "The interpreter that the script will be called with, a builtin or a name configured on the runner.",
+            ))
+            .item(
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .enum_values(Some(Self::BUILTIN.iter().map(ScriptInterpreter::name))),
+            )
+            .item(
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .pattern(Some("^[A-Za-z0-9._-]+$")),
+            )
+            .into()
+    }
+}
+
+#[cfg(feature = "schema")]
+impl utoipa::ToSchema for ScriptInterpreter {}
+
+impl std::fmt::Display for ScriptInterpreter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for ScriptInterpreter {
+    type Err = String;
+
+    /// Parses the name of a builtin interpreter, or else a [`ScriptInterpreter::Custom`] one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(builtin) = Self::BUILTIN
+            .into_iter()
+            .find(|builtin| builtin.name() == s)
+        {
+            return Ok(builtin);
+        }
+        let valid = s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if s.is_empty() || !valid {
+            return Err(format!("Invalid interpreter name: {s}"));
+        }
+        Ok(ScriptInterpreter::Custom(String::from(s)))
+    }
+}
+
+/// Deserializes by [`FromStr`](std::str::FromStr), such that names are validated like in queries.
+impl TryFrom<String> for ScriptInterpreter {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl From<ScriptInterpreter> for String {
+    fn from(interpreter: ScriptInterpreter) -> Self {
+        match interpreter {
+            ScriptInterpreter::Custom(name) => name,
+            builtin => String::from(builtin.name()),
+        }
+    }
+}
+
+/// The json response format for `/api/run` and `/api/runscript`.
+///
+/// # Serialized Examples
+/// A completed command:
+/// ```
+/// # let ser = r#"
+/// {
+///     "id": 73001,
+///     "status": "Completed",
+///     "exit_code": 1,
+///     "time_taken": {
+///         "secs": 21,
+///         "nanos": 800000
+///     }
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunResponse
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert!(matches!(deser.status, rusty_runner_api::api::RunStatus::Completed { .. }));
+/// ```
+/// A command that was terminated by a signal:
+/// ```
+/// # let ser = r#"
+/// {
+///     "id": 73002,
+///     "status": "Completed",
+///     "exit_code": -1001,
+///     "signal": {
+///         "number": 6,
+///         "name": "SIGABRT"
+///     },
+///     "core_dumped": true,
+///     "time_taken": {
+///         "secs": 0,
+///         "nanos": 3000000
+///     }
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunResponse
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert!(matches!(deser.status, rusty_runner_api::api::RunStatus::Completed { core_dumped: true, .. }));
+/// ```
+/// A job with a client chosen id and labels:
+/// ```
+/// # let ser = r#"
+/// {
+///     "id": 73003,
+///     "job_id": "deploy-42",
+///     "labels": {
+///         "pipeline": "ci",
+///         "sha": "abc123"
+///     },
+///     "status": "Completed",
+///     "exit_code": 0,
+///     "time_taken": {
+///         "secs": 3,
+///         "nanos": 0
+///     }
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunResponse
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(deser.labels["pipeline"], "ci");
+/// ```
+/// A command that could not be executed:
+/// ```
+/// # let ser = r#"
+/// {
+///     "id": 1234567890,
+///     "status": "Failure",
+///     "reason": "Not supported"
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::RunResponse
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert!(matches!(deser.status, rusty_runner_api::api::RunStatus::Failure { .. }));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct RunResponse {
+    pub id: u64,
+    /// The `job_id` of the request, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// The `labels` of the request.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    #[serde(flatten)]
+    pub status: RunStatus,
+}
+
+impl RunResponse {
+    /// The response of the job `id` without a `job_id` and labels.
+    #[must_use]
+    pub fn new(id: u64, status: RunStatus) -> Self {
+        Self {
+            id,
+            job_id: None,
+            labels: HashMap::new(),
+            status,
+        }
+    }
+}
+
+/// The outcome of a command.
+///
+/// If the command could be started, then this is a [`Completed`](RunStatus::Completed)
 /// even if the command itself exited non-successfully.
 /// Otherwise this is [`Failure`](RunStatus::Failure).
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
 #[serde(tag = "status")]
 pub enum RunStatus {
     /// Completely ran the command. The command may have succeeded of failed.
+    ///
+    /// Further fields may be added, construct it by [`RunStatus::completed`].
+    #[non_exhaustive]
     Completed {
         /// Exit code of the command or -1001 if terminated by a signal.
         /// This may get only return the least byte.
+        ///
+        /// See `signal` for the signal that terminated the command.
         exit_code: i32,
+        /// The signal that terminated the command, if any. Only available on unix.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signal: Option<TerminationSignal>,
+        /// `true` if the command produced a core dump when terminated by `signal`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        core_dumped: bool,
+        /// If `kill_orphans` is set, the processes that were still running in the
+        /// process group after the command exited and had to be killed.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        killed_orphans: Vec<OrphanProcess>,
         /// The wall time it took to run.
+        #[cfg_attr(feature = "schema", schema(value_type = DurationSchema))]
         time_taken: Duration,
-        /// If `return_stdout` is set, this returns the raw `stdout` bytes.
-        #[serde(skip_serializing_if = "Option::is_none")]
-        stdout: Option<Vec<u8>>,
-        /// If `return_stderr` is set, this returns the raw `stderr` bytes.
-        #[serde(skip_serializing_if = "Option::is_none")]
-        stderr: Option<Vec<u8>>,
+        /// If `return_stdout` is set, this returns the raw `stdout` bytes, encoded as requested.
+        /// If `tty` is set, this is the merged terminal output instead.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stdout: Option<OutputBytes>,
+        /// If `return_stderr` is set, this returns the raw `stderr` bytes, encoded as requested.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stderr: Option<OutputBytes>,
+        /// If `transcript` is set, the chunks of `stdout` and `stderr` in the order they were read.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transcript: Option<Vec<TranscriptChunk>>,
+        /// The total number of bytes written to `stdout`, only set if it was truncated.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stdout_total_bytes: Option<u64>,
+        /// The total number of bytes written to `stderr`, only set if it was truncated.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stderr_total_bytes: Option<u64>,
     },
     /// Failed to run the command due to internal reasons.
     /// Does not indicate a command that ran with a non-success exit code, but
     /// rather that the command couldn't even be started.
     Failure { reason: String },
 }
+
+impl RunStatus {
+    /// A [`Completed`](RunStatus::Completed) status without signal, output or orphans.
+    #[must_use]
+    pub fn completed(exit_code: i32, time_taken: Duration) -> Self {
+        RunStatus::Completed {
+            exit_code,
+            signal: None,
+            core_dumped: false,
+            killed_orphans: Vec::new(),
+            time_taken,
+            stdout: None,
+            stderr: None,
+            transcript: None,
+            stdout_total_bytes: None,
+            stderr_total_bytes: None,
+        }
+    }
+
+    /// `true` if the command completed with exit code 0.
+    ///
+    /// ```
+    /// # let response: rusty_runner_api::api::RunResponse = serde_json::from_str(r#"
+    /// # {"id": 1, "status": "Completed", "exit_code": 0, "time_taken": {"secs": 0, "nanos": 0}, "stdout": "Hi\n"}
+    /// # "#).expect("failed parsing");
+    /// assert!(response.status.success());
+    /// assert_eq!(response.status.exit_code(), Some(0));
+    /// assert_eq!(response.status.stdout_str().as_deref(), Some("Hi\n"));
+    /// ```
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.exit_code() == Some(0)
+    }
+
+    /// The exit code of the command, if it completed without being terminated by a signal.
+    #[must_use]
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            RunStatus::Completed {
+                exit_code,
+                signal: None,
+                ..
+            } => Some(*exit_code),
+            _ => None,
+        }
+    }
+
+    /// The returned `stdout` as a string, replacing invalid utf8 with `U+FFFD`.
+    #[must_use]
+    pub fn stdout_str(&self) -> Option<std::borrow::Cow<'_, str>> {
+        match self {
+            RunStatus::Completed {
+                stdout: Some(stdout),
+                ..
+            } => Some(String::from_utf8_lossy(stdout)),
+            _ => None,
+        }
+    }
+
+    /// The returned `stderr` as a string, replacing invalid utf8 with `U+FFFD`.
+    #[must_use]
+    pub fn stderr_str(&self) -> Option<std::borrow::Cow<'_, str>> {
+        match self {
+            RunStatus::Completed {
+                stderr: Some(stderr),
+                ..
+            } => Some(String::from_utf8_lossy(stderr)),
+            _ => None,
+        }
+    }
+
+    /// The reason if the command could not be run.
+    #[must_use]
+    pub fn failure_reason(&self) -> Option<&str> {
+        match self {
+            RunStatus::Failure { reason } => Some(reason),
+            RunStatus::Completed { .. } => None,
+        }
+    }
+}
+
+/// A unix signal that terminated a command.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// {
+///     "number": 11,
+///     "name": "SIGSEGV"
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::TerminationSignal
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(deser.number, 11);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct TerminationSignal {
+    /// The raw signal number, which is platform dependent.
+    pub number: i32,
+    /// The signal name, e.g. `SIGKILL`, or `UNKNOWN` if the runner doesn't know the number.
+    pub name: String,
+}
+
+impl TerminationSignal {
+    #[must_use]
+    pub fn new(number: i32, name: impl Into<String>) -> Self {
+        Self {
+            number,
+            name: name.into(),
+        }
+    }
+}
+
+/// A process that outlived its command and was killed by the runner.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// {
+///     "pid": 4242,
+///     "name": "sleep"
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::OrphanProcess
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert_eq!(deser.name, "sleep");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct OrphanProcess {
+    /// The process id.
+    pub pid: u32,
+    /// The executable name of the process, possibly truncated.
+    pub name: String,
+}
+
+impl OrphanProcess {
+    #[must_use]
+    pub fn new(pid: u32, name: impl Into<String>) -> Self {
+        Self {
+            pid,
+            name: name.into(),
+        }
+    }
+}
+
+/// A chunk of output in a transcript, which interleaves `stdout` and `stderr`.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// {
+///     "stream": "stderr",
+///     "elapsed": {
+///         "secs": 1,
+///         "nanos": 500000000
+///     },
+///     "data": "warning: unused variable\n"
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::TranscriptChunk
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert!(matches!(deser.stream, rusty_runner_api::api::OutputStream::Stderr));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct TranscriptChunk {
+    /// The stream the chunk was written to.
+    pub stream: OutputStream,
+    /// The time since the command was started, when the chunk was read.
+    #[cfg_attr(feature = "schema", schema(value_type = DurationSchema))]
+    pub elapsed: Duration,
+    /// The output, encoded as requested by `output_encoding`.
+    pub data: OutputBytes,
+}
+
+impl TranscriptChunk {
+    #[must_use]
+    pub fn new(stream: OutputStream, elapsed: Duration, data: OutputBytes) -> Self {
+        Self {
+            stream,
+            elapsed,
+            data,
+        }
+    }
+}
+
+/// An output stream of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// The query schema for `GET /api/jobs`.
+///
+/// Jobs are only recorded if the runner has a job history configured.
+/// As a client chosen `job_id` is only unique among running jobs, jobs are looked up by it here,
+/// while `GET /api/job/{id}` takes the runner assigned id.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// since=1718000000&status=Failure&job_id=deploy-42&limit=10
+/// # "#;
+/// # let deser: rusty_runner_api::api::JobsQuery
+/// #    = serde_urlencoded::from_str(ser.trim()).expect("failed parsing");
+/// # assert_eq!(deser.limit, Some(10));
+/// # assert_eq!(deser.job_id.as_deref(), Some("deploy-42"));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "schema", into_params(parameter_in = Query))]
+#[non_exhaustive]
+pub struct JobsQuery {
+    /// Only return jobs started at or after this unix timestamp in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    /// Only return jobs with this status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<JobStatus>,
+    /// Only return jobs with this client chosen `job_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// The maximum number of jobs to return, most recently finished first. Defaults to 100.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// The status of a job, matching the variants of [`RunStatus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub enum JobStatus {
+    Completed,
+    Failure,
+}
+
+/// A recorded job as returned by `GET /api/job/{id}` and `GET /api/jobs`.
+///
+/// Captured output is capped by the runner.
+///
+/// # Serialized Example
+/// ```
+/// # let ser = r#"
+/// {
+///     "id": 73001,
+///     "started_at": {
+///         "secs_since_epoch": 1718000000,
+///         "nanos_since_epoch": 0
+///     },
+///     "request": {
+///         "endpoint": "run",
+///         "command": "echo",
+///         "arguments": ["Hello"]
+///     },
+///     "status": "Completed",
+///     "exit_code": 0,
+///     "time_taken": {
+///         "secs": 0,
+///         "nanos": 800000
+///     }
+/// }
+/// # "#;
+/// # let deser: rusty_runner_api::api::JobRecord
+/// #    = serde_json::from_str(ser).expect("failed parsing");
+/// # assert!(matches!(deser.request, rusty_runner_api::api::JobRequest::Run(_)));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[non_exhaustive]
+pub struct JobRecord {
+    pub id: u64,
+    /// When the runner received the job.
+    #[cfg_attr(feature = "schema", schema(value_type = SystemTimeSchema))]
+    pub started_at: SystemTime,
+    /// The request that started the job.
+    pub request: JobRequest,
+    #[serde(flatten)]
+    pub status: RunStatus,
+}
+
+impl JobRecord {
+    #[must_use]
+    pub fn new(id: u64, started_at: SystemTime, request: JobRequest, status: RunStatus) -> Self {
+        Self {
+            id,
+            started_at,
+            request,
+            status,
+        }
+    }
+
+    /// The client chosen `job_id` of the request, if any.
+    #[must_use]
+    pub fn job_id(&self) -> Option<&str> {
+        match &self.request {
+            JobRequest::Run(request) => request.job_id.as_deref(),
+            JobRequest::RunScript { query, .. } | JobRequest::RunScriptBundle { query, .. } => {
+                query.job_id.as_deref()
+            }
+            JobRequest::RunScriptJson(request) => request.job_id.as_deref(),
+            JobRequest::RunTemplate { request, .. } => request.job_id.as_deref(),
+        }
+    }
+
+    /// The status of the job without its details.
+    #[must_use]
+    pub fn job_status(&self) -> JobStatus {
+        match self.status {
+            RunStatus::Completed { .. } => JobStatus::Completed,
+            RunStatus::Failure { .. } => JobStatus::Failure,
+        }
+    }
+}
+
+/// The request of a [`JobRecord`], tagged by the `endpoint` it was sent to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(tag = "endpoint", rename_all = "lowercase")]
+pub enum JobRequest {
+    /// A `POST /api/run` request.
+    Run(RunRequest),
+    /// A `POST /api/runscript` request.
+    RunScript {
+        query: RunScriptQuery,
+        script: String,
+    },
+    /// A `POST /api/runscript/json` request.
+    #[serde(rename = "runscript/json")]
+    RunScriptJson(RunScriptRequest),
+    /// A `POST /api/runscript/bundle` request with the names of its files.
+    #[serde(rename = "runscript/bundle")]
+    RunScriptBundle {
+        query: RunScriptQuery,
+        bundle: BundleQuery,
+        files: Vec<String>,
+    },
+    /// A `POST /api/scripts/{name}/run` request.
+    #[serde(rename = "scripts/run")]
+    RunTemplate {
+        name: String,
+        request: RunTemplateRequest,
+    },
+}
@@ -0,0 +1,329 @@
+//! A typed async client for the api, built on [`reqwest`].
+//!
+//! Requires the `client` feature. The `blocking` feature adds a synchronous variant in `client::blocking`.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> Result<(), rusty_runner_api::client::ClientError> {
+//! use rusty_runner_api::api::RunRequest;
+//! use rusty_runner_api::client::RunnerClient;
+//!
+//! let client = RunnerClient::new("http://localhost:8000")?;
+//! let info = client.info().await?;
+//! println!("Connected to {}", info.computer_name);
+//!
+//! let request = RunRequest::new("echo").args(["Hello", "World"]).capture_stdout();
+//! let response = client.run(&request, None).await?;
+//! if let Some(stdout) = response.status.stdout_str() {
+//!     println!("Printed {stdout}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::{
+    BundleQuery, InfoResponse, RunRequest, RunResponse, RunScriptQuery, RunScriptRequest,
+    RunTemplateRequest, ScriptTemplate,
+};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder, Response, Url};
+use std::fmt;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// A client for a single runner.
+///
+/// Cloning is cheap, as the underlying connection pool is shared.
+#[derive(Debug, Clone)]
+pub struct RunnerClient {
+    base_url: Url,
+    http: Client,
+}
+
+impl RunnerClient {
+    /// Creates a client for the runner at `base_url`, e.g. `http://localhost:8000`.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::InvalidUrl`] if `base_url` cannot be parsed.
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        Self::with_http_client(base_url, Client::new())
+    }
+
+    /// Creates a client using a preconfigured [`reqwest::Client`], e.g. with custom timeouts or headers.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::InvalidUrl`] if `base_url` cannot be parsed.
+    pub fn with_http_client(base_url: &str, http: Client) -> Result<Self, ClientError> {
+        let mut base_url =
+            Url::parse(base_url).map_err(|e| ClientError::InvalidUrl(e.to_string()))?;
+        if base_url.cannot_be_a_base() {
+            return Err(ClientError::InvalidUrl(format!(
+                "{base_url} cannot be a base url"
+            )));
+        }
+        // Ensure the api paths are joined to the full base path.
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        Ok(Self { base_url, http })
+    }
+
+    /// `GET /api/info`
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn info(&self) -> Result<InfoResponse, ClientError> {
+        let response = self.http.get(self.url("api/info")?).send().await?;
+        Ok(error_for_status(response).await?.json().await?)
+    }
+
+    /// `POST /api/run`
+    ///
+    /// Requests rejected by the runner, e.g. for a disallowed user,
+    /// are returned as [`RunStatus::Failure`](crate::api::RunStatus::Failure).
+    ///
+    /// With an `idempotency_key`, retries of the same request return the response of the original job
+    /// instead of running it again. Reusing a key for a different request is rejected.
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn run(
+        &self,
+        request: &RunRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        let response = self
+            .post("api/run", idempotency_key)?
+            .json(request)
+            .send()
+            .await?;
+        run_response(response).await
+    }
+
+    /// `POST /api/runscript`, running the `script` with the interpreter given in the `query`.
+    ///
+    /// Requests rejected by the runner, e.g. for an unsupported interpreter,
+    /// are returned as [`RunStatus::Failure`](crate::api::RunStatus::Failure).
+    /// The `idempotency_key` works as for [`RunnerClient::run`].
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn run_script(
+        &self,
+        query: &RunScriptQuery,
+        script: impl Into<String>,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        let response = self
+            .post("api/runscript", idempotency_key)?
+            .query(query)
+            .body(script.into())
+            .send()
+            .await?;
+        run_response(response).await
+    }
+
+    /// `POST /api/runscript/json`, running the script of the `request`.
+    ///
+    /// Unlike [`RunnerClient::run_script`], the script and its arguments are sent as a json body.
+    /// Requests rejected by the runner are returned as [`RunStatus::Failure`](crate::api::RunStatus::Failure).
+    /// The `idempotency_key` works as for [`RunnerClient::run`].
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn run_script_json(
+        &self,
+        request: &RunScriptRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        let response = self
+            .post("api/runscript/json", idempotency_key)?
+            .json(request)
+            .send()
+            .await?;
+        run_response(response).await
+    }
+
+    /// `POST /api/runscript/bundle`, uploading the `files` by their relative paths
+    /// and running the entrypoint given in the `bundle` query.
+    ///
+    /// Requests rejected by the runner, e.g. for a missing entrypoint,
+    /// are returned as [`RunStatus::Failure`](crate::api::RunStatus::Failure).
+    /// The `idempotency_key` works as for [`RunnerClient::run`].
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn run_script_bundle<N, C>(
+        &self,
+        query: &RunScriptQuery,
+        bundle: &BundleQuery,
+        files: impl IntoIterator<Item = (N, C)>,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError>
+    where
+        N: Into<String>,
+        C: Into<Vec<u8>>,
+    {
+        let form = files
+            .into_iter()
+            .fold(Form::new(), |form, (name, content)| {
+                let name = name.into();
+                form.part(name.clone(), Part::bytes(content.into()).file_name(name))
+            });
+        let response = self
+            .post("api/runscript/bundle", idempotency_key)?
+            .query(query)
+            .query(bundle)
+            .multipart(form)
+            .send()
+            .await?;
+        run_response(response).await
+    }
+
+    /// `GET /api/scripts`, listing the script templates of the runner.
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub async fn scripts(&self) -> Result<Vec<ScriptTemplate>, ClientError> {
+        let response = self.http.get(self.url("api/scripts")?).send().await?;
+        Ok(error_for_status(response).await?.json().await?)
+    }
+
+    /// `POST /api/scripts/{name}/run`, running the script template `name` with the parameters of the `request`.
+    ///
+    /// Requests rejected by the runner, e.g. for a missing parameter,
+    /// are returned as [`RunStatus::Failure`](crate::api::RunStatus::Failure).
+    /// The `idempotency_key` works as for [`RunnerClient::run`].
+    ///
+    /// # Errors
+    /// Returns [`ClientError::Status`] with `404` if there is no such template, otherwise see [`ClientError`].
+    pub async fn run_template(
+        &self,
+        name: &str,
+        request: &RunTemplateRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        let mut url = self.url("api/scripts/")?;
+        url.path_segments_mut()
+            .map_err(|()| ClientError::InvalidUrl(String::from("url cannot be a base url")))?
+            .pop_if_empty()
+            .extend([name, "run"]);
+        let response = with_idempotency_key(self.http.post(url), idempotency_key)
+            .json(request)
+            .send()
+            .await?;
+        run_response(response).await
+    }
+
+    /// `GET /api/file/{path}`, returning the content of the file relative to the working directory.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::Status`] with `404` if the file doesn't exist, otherwise see [`ClientError`].
+    pub async fn fetch_file(&self, path: &str) -> Result<Vec<u8>, ClientError> {
+        let mut url = self.url("api/file/")?;
+        url.path_segments_mut()
+            .map_err(|()| ClientError::InvalidUrl(String::from("url cannot be a base url")))?
+            .pop_if_empty()
+            .extend(path.split('/').filter(|segment| !segment.is_empty()));
+        let response = self.http.get(url).send().await?;
+        Ok(error_for_status(response).await?.bytes().await?.to_vec())
+    }
+
+    /// A `POST` request to `path`, sending the `idempotency_key` if given.
+    fn post(
+        &self,
+        path: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<RequestBuilder, ClientError> {
+        Ok(with_idempotency_key(
+            self.http.post(self.url(path)?),
+            idempotency_key,
+        ))
+    }
+
+    fn url(&self, path: &str) -> Result<Url, ClientError> {
+        self.base_url
+            .join(path)
+            .map_err(|e| ClientError::InvalidUrl(e.to_string()))
+    }
+}
+
+fn with_idempotency_key(request: RequestBuilder, idempotency_key: Option<&str>) -> RequestBuilder {
+    match idempotency_key {
+        Some(key) => request.header("Idempotency-Key", key),
+        None => request,
+    }
+}
+
+/// Decodes the [`RunResponse`], which the runner also returns for rejected requests.
+async fn run_response(response: Response) -> Result<RunResponse, ClientError> {
+    if response.status().is_success() {
+        return Ok(response.json().await?);
+    }
+    let status = response.status().as_u16();
+    let body = response.text().await?;
+    match serde_json::from_str(&body) {
+        Ok(response) => Ok(response),
+        Err(_) => Err(ClientError::Status { status, body }),
+    }
+}
+
+async fn error_for_status(response: Response) -> Result<Response, ClientError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    Err(ClientError::Status {
+        status: response.status().as_u16(),
+        body: response.text().await?,
+    })
+}
+
+/// The errors of a [`RunnerClient`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClientError {
+    /// The url of the runner or the requested path is invalid.
+    InvalidUrl(String),
+    /// The request could not be sent or the response could not be read or decoded.
+    Http(reqwest::Error),
+    /// The runner responded with an unexpected error status, e.g. `404` for a missing file.
+    Status {
+        /// The http status code.
+        status: u16,
+        /// The response body, commonly a short error message.
+        body: String,
+    },
+    /// A local io error, e.g. when starting the runtime of the `blocking` client.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(reason) => write!(f, "invalid url: {reason}"),
+            ClientError::Http(e) => write!(f, "http error: {e}"),
+            ClientError::Status { status, body } => {
+                write!(f, "runner responded with status {status}: {body}")
+            }
+            ClientError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http(e) => Some(e),
+            ClientError::Io(e) => Some(e),
+            ClientError::InvalidUrl(_) | ClientError::Status { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
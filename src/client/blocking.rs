@@ -0,0 +1,150 @@
+//! A blocking variant of the [`RunnerClient`](super::RunnerClient) for synchronous code.
+//!
+//! Requires the `blocking` feature.
+//!
+//! # Example
+//! ```no_run
+//! use rusty_runner_api::client::blocking::RunnerClient;
+//!
+//! let client = RunnerClient::new("http://localhost:8000")?;
+//! println!("Connected to {}", client.info()?.computer_name);
+//! # Ok::<(), rusty_runner_api::client::ClientError>(())
+//! ```
+
+use super::ClientError;
+use crate::api::{
+    BundleQuery, InfoResponse, RunRequest, RunResponse, RunScriptQuery, RunScriptRequest,
+    RunTemplateRequest, ScriptTemplate,
+};
+use tokio::runtime::Runtime;
+
+/// A client for a single runner, which blocks on each call.
+///
+/// Each client runs its own single threaded runtime, so it must not be used within an async context.
+#[derive(Debug)]
+pub struct RunnerClient {
+    inner: super::RunnerClient,
+    runtime: Runtime,
+}
+
+impl RunnerClient {
+    /// Creates a client for the runner at `base_url`, e.g. `http://localhost:8000`.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::InvalidUrl`] if `base_url` cannot be parsed
+    /// or [`ClientError::Io`] if the runtime cannot be started.
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        Self::from_async(super::RunnerClient::new(base_url)?)
+    }
+
+    /// Wraps an async client, e.g. one with a preconfigured [`reqwest::Client`].
+    ///
+    /// # Errors
+    /// Returns [`ClientError::Io`] if the runtime cannot be started.
+    pub fn from_async(inner: super::RunnerClient) -> Result<Self, ClientError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(ClientError::Io)?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// See [`RunnerClient::info`](super::RunnerClient::info).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn info(&self) -> Result<InfoResponse, ClientError> {
+        self.runtime.block_on(self.inner.info())
+    }
+
+    /// See [`RunnerClient::run`](super::RunnerClient::run).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn run(
+        &self,
+        request: &RunRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        self.runtime
+            .block_on(self.inner.run(request, idempotency_key))
+    }
+
+    /// See [`RunnerClient::run_script`](super::RunnerClient::run_script).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn run_script(
+        &self,
+        query: &RunScriptQuery,
+        script: impl Into<String>,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        self.runtime
+            .block_on(self.inner.run_script(query, script, idempotency_key))
+    }
+
+    /// See [`RunnerClient::run_script_json`](super::RunnerClient::run_script_json).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn run_script_json(
+        &self,
+        request: &RunScriptRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        self.runtime
+            .block_on(self.inner.run_script_json(request, idempotency_key))
+    }
+
+    /// See [`RunnerClient::run_script_bundle`](super::RunnerClient::run_script_bundle).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn run_script_bundle<N, C>(
+        &self,
+        query: &RunScriptQuery,
+        bundle: &BundleQuery,
+        files: impl IntoIterator<Item = (N, C)>,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError>
+    where
+        N: Into<String>,
+        C: Into<Vec<u8>>,
+    {
+        self.runtime.block_on(
+            self.inner
+                .run_script_bundle(query, bundle, files, idempotency_key),
+        )
+    }
+
+    /// See [`RunnerClient::scripts`](super::RunnerClient::scripts).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn scripts(&self) -> Result<Vec<ScriptTemplate>, ClientError> {
+        self.runtime.block_on(self.inner.scripts())
+    }
+
+    /// See [`RunnerClient::run_template`](super::RunnerClient::run_template).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn run_template(
+        &self,
+        name: &str,
+        request: &RunTemplateRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RunResponse, ClientError> {
+        self.runtime
+            .block_on(self.inner.run_template(name, request, idempotency_key))
+    }
+
+    /// See [`RunnerClient::fetch_file`](super::RunnerClient::fetch_file).
+    ///
+    /// # Errors
+    /// See [`ClientError`].
+    pub fn fetch_file(&self, path: &str) -> Result<Vec<u8>, ClientError> {
+        self.runtime.block_on(self.inner.fetch_file(path))
+    }
+}
@@ -6,10 +6,32 @@
 //!
 //! ## Usage
 //! For the complete usage, see the serde structs in [`api`].
+//! They are `#[non_exhaustive]`, so requests are constructed by their builders,
+//! e.g. `RunRequest::new("echo").arg("Hello").capture_stdout()`.
 //! * `GET /api/info` returns an informative [`api::InfoResponse`] object.
+//!   It lists the supported interpreters, [`api::features`] and limits, see [`api::InfoResponse::supports`].
 //! * `POST /api/run` runs a command analogous to [`std::process::Command`].
 //! * `POST /api/runscript` runs the body with a given interpreter.
+//! * `POST /api/runscript/json` runs a script with arguments given as a [`api::RunScriptRequest`] json body.
+//! * `POST /api/runscript/bundle` runs the entrypoint of several uploaded files, see [`api::BundleQuery`].
+//! * `GET /api/scripts` lists the [`api::ScriptTemplate`]s of the server's script library.
+//! * `POST /api/scripts/{name}/run` runs a template with parameters, see [`api::RunTemplateRequest`].
 //! * `GET /api/file/{path}` fetches a file from the servers working directory.
+//! * `GET /api/jobs` lists recorded [`api::JobRecord`]s, filtered by [`api::JobsQuery`].
+//! * `GET /api/job/{id}` returns a single recorded [`api::JobRecord`] by the runner assigned id.
+//!   Jobs with a client chosen `job_id` are looked up by `GET /api/jobs?job_id=...`.
+//! * `GET /api/openapi.json` returns the OpenAPI document of the endpoints.
+//!   The schemas are derived from the [`api`] types with the `schema` feature.
+//!
+//! ## Client
+//! With the `client` feature, [`client::RunnerClient`] provides typed async methods for the endpoints,
+//! so the urls, query strings and json bodies don't have to be built by hand.
+//! The `blocking` feature adds a synchronous variant.
+//!
+//! ## Command line
+//! The `rusty-runner` binary of the `rusty-runner-cli` package wraps the client for shell scripts.
+//! It prints the remote `stdout` and `stderr` to the local streams and exits with the remote exit code,
+//! e.g. `rusty-runner --url http://localhost:8000 run -- ls -la`.
 //!
 //! ## Working with files
 //! The working directory of the executed commands is implementation defined,
@@ -27,8 +49,15 @@
 //! In a future version of the api, a [`RunStatus::Pending`](api::RunStatus) variant
 //! and a status poll endpoint might be added.
 //!
+//! ## Retrying requests
+//! Both `POST /api/run` and `POST /api/runscript` accept an `Idempotency-Key` header.
+//! Retrying a request with the same key returns the original [`api::RunResponse`] instead of running it again,
+//! waiting for the original job if it is still running. How long keys are remembered is up to the server.
+//!
 //! ## Security
 //! The api does not include any security measures, this is *remote execution as a service!*.
 //! Make sure it is only reachable from trusted hosts. E.g. by means of ssh port forwarding.
 
 pub mod api;
+#[cfg(feature = "client")]
+pub mod client;